@@ -1,4 +1,15 @@
 fn main() {
+    // `gl_check` turns on the per-call GL error checks; they never make it into release builds.
+    println!("cargo::rustc-check-cfg=cfg(gl_check)");
+    if std::env::var_os("CARGO_FEATURE_GL_CHECK").is_some()
+        && std::env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some()
+    {
+        println!("cargo::rustc-cfg=gl_check");
+    }
+    // The `host` feature builds against a stand-in GL, so there is nothing to link.
+    if std::env::var_os("CARGO_FEATURE_HOST").is_some() {
+        return;
+    }
     for i in [
         "vitaGL",
         "vitashark",
@@ -5,7 +5,7 @@ use vita_gl_helpers::{
     draw::{Elements, ElementsBufU16, Mode},
     initialise_default,
     program::link_program,
-    shader::load_shader,
+    shader::{ShaderStage, load_shader},
     swap_buffers, uniform_table,
     uniforms::UniformTable,
 };
@@ -37,18 +37,18 @@ const POS_FORMAT: AttributeFormat = AttributeFormat {
 };
 
 fn main() {
-    initialise_default();
-    let vertex_shader = load_shader(include_str!("vert.cg"), gl::VERTEX_SHADER).expect("Oops!");
-    let fragment_shader = load_shader(include_str!("frag.cg"), gl::FRAGMENT_SHADER).expect("Oops!");
-    let program = link_program(vertex_shader, fragment_shader).expect("Oops!");
-    let utable = MyUniformTable::with_locations_from(&program).expect("Oops!");
-    let atable = MyAttributeTable::with_locations_from(&program).expect("Oops!");
+    let ctx = initialise_default().expect("vitaGL failed to start");
+    let vertex_shader = load_shader(&ctx, include_str!("vert.cg"), ShaderStage::Vertex).expect("Oops!");
+    let fragment_shader = load_shader(&ctx, include_str!("frag.cg"), ShaderStage::Fragment).expect("Oops!");
+    let program = link_program(&ctx, vertex_shader, fragment_shader).expect("Oops!");
+    let utable = MyUniformTable::with_locations_from(&ctx, &program).expect("Oops!");
+    let atable = MyAttributeTable::with_locations_from(&ctx, &program).expect("Oops!");
     let mut buffers = [Buffer::default(); 4];
-    buffers.gen_buffers();
-    buffers[0].data(gl::ARRAY_BUFFER, POSITIONS, gl::STATIC_DRAW);
-    buffers[1].data(gl::ARRAY_BUFFER, TOP_COLORS, gl::STATIC_DRAW);
-    buffers[2].data(gl::ARRAY_BUFFER, BOTTOM_COLORS, gl::STATIC_DRAW);
-    buffers[3].data(gl::ELEMENT_ARRAY_BUFFER, INDICES, gl::STATIC_DRAW);
+    buffers.gen_buffers(&ctx);
+    buffers[0].data(&ctx, gl::ARRAY_BUFFER, POSITIONS, gl::STATIC_DRAW);
+    buffers[1].data(&ctx, gl::ARRAY_BUFFER, TOP_COLORS, gl::STATIC_DRAW);
+    buffers[2].data(&ctx, gl::ARRAY_BUFFER, BOTTOM_COLORS, gl::STATIC_DRAW);
+    buffers[3].data(&ctx, gl::ELEMENT_ARRAY_BUFFER, INDICES, gl::STATIC_DRAW);
     unsafe {
         gl::ClearColor(1.0, 1.0, 1.0, 1.0);
     }
@@ -56,17 +56,17 @@ fn main() {
         unsafe {
             gl::Clear(gl::COLOR_BUFFER_BIT);
         }
-        program.use_me();
-        atable.enable_all();
-        buffers[0].bind_to(atable.pos, POS_FORMAT, 0, 0);
-        buffers[1].bind_to(atable.color_top, COLOR_FORMAT, 0, 0);
-        buffers[2].bind_to(atable.color_bottom, COLOR_FORMAT, 0, 0);
-        utable.rect_dim.set([0.25, -0.5]);
+        program.use_me(&ctx);
+        atable.enable_all(&ctx);
+        buffers[0].bind_to(&ctx, atable.pos, POS_FORMAT, 0, 0);
+        buffers[1].bind_to(&ctx, atable.color_top, COLOR_FORMAT, 0, 0);
+        buffers[2].bind_to(&ctx, atable.color_bottom, COLOR_FORMAT, 0, 0);
+        utable.rect_dim.set(&ctx, [0.25, -0.5]);
         ElementsBufU16 {
             indices: buffers[3],
             len: 4,
         }
-        .draw_instanced(Mode::Quads, 4);
-        swap_buffers();
+        .draw_instanced(&ctx, Mode::Quads, 4);
+        swap_buffers(&ctx);
     }
 }
@@ -3,12 +3,19 @@ use std::ffi::c_void;
 use derive_more::From;
 use gl::types::{GLsizei, GLuint};
 
-use crate::program::Program;
+use crate::{
+    GlContext,
+    errors::check_wrapper,
+    program::{Program, TableLookup},
+};
+
+#[cfg(feature = "derive")]
+pub use vita_gl_helpers_derive::AttributeTable;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct Attribute(pub GLuint);
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(i32)]
 pub enum AttributeSize {
     ONE = 1,
@@ -17,7 +24,7 @@ pub enum AttributeSize {
     FOUR,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum AttributeType {
     Byte = gl::BYTE,
@@ -28,7 +35,7 @@ pub enum AttributeType {
     Float = gl::FLOAT,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct AttributeFormat {
     pub size: AttributeSize,
     pub type_: AttributeType,
@@ -36,23 +43,50 @@ pub struct AttributeFormat {
 }
 
 impl Attribute {
+    /// An optional attribute the shader doesn't have. Everything done with it is a no-op.
+    pub const MISSING: Attribute = Attribute(GLuint::MAX);
+
+    pub fn is_missing(&self) -> bool {
+        *self == Self::MISSING
+    }
     ///Note on vitaGL, the only recognised divisors are 0 and 1
-    pub fn divisor(&self, divisor: GLuint) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn divisor(&self, _ctx: &GlContext, divisor: GLuint) {
+        if self.is_missing() {
+            return;
+        }
         unsafe {
             gl::VertexAttribDivisor(self.0, divisor);
         }
+        check_wrapper!("Attribute::divisor", self.0, divisor);
     }
-    pub fn enable(&self) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn enable(&self, _ctx: &GlContext) {
+        if self.is_missing() {
+            return;
+        }
         unsafe {
             gl::EnableVertexAttribArray(self.0);
         }
+        check_wrapper!("Attribute::enable", self.0);
     }
-    pub fn disable(&self) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn disable(&self, _ctx: &GlContext) {
+        if self.is_missing() {
+            return;
+        }
         unsafe {
             gl::DisableVertexAttribArray(self.0);
         }
+        check_wrapper!("Attribute::disable", self.0);
     }
+    /// # Safety
+    /// `pointer` is an offset into the bound `ARRAY_BUFFER`, or a client pointer that must stay valid until the draw call.
+    #[cfg_attr(gl_check, track_caller)]
     pub unsafe fn pointer(&self, format: AttributeFormat, stride: GLsizei, pointer: *const c_void) {
+        if self.is_missing() {
+            return;
+        }
         unsafe {
             gl::VertexAttribPointer(
                 self.0,
@@ -67,6 +101,7 @@ impl Attribute {
                 pointer,
             );
         }
+        check_wrapper!("Attribute::pointer", self.0, format, stride, pointer);
     }
 }
 
@@ -81,42 +116,159 @@ impl std::fmt::Display for MissingAttributes {
 
 impl std::error::Error for MissingAttributes {}
 
+/// What an [`AttributeTable`] field expects of its attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeField {
+    pub name: &'static str,
+    pub optional: bool,
+}
+
+/// A set of attributes looked up together. Usually derived:
+/// ```rust
+/// use vita_gl_helpers::attribute::{Attribute, AttributeTable};
+/// #[derive(AttributeTable, Default, Clone, Copy)]
+/// pub struct MyAttributes {
+///     #[gl(name = "aPos")]
+///     pub pos: Attribute,
+///     #[gl(name = "aColor")]
+///     pub color: Attribute,
+/// }
+/// ```
+/// or made with [`attribute_table!`](crate::attribute_table).
+///
+/// Fields marked `#[gl(optional)]` (`#[optional]` in the macro) are left as
+/// [`Attribute::MISSING`] when the shader doesn't have them, instead of failing.
 pub trait AttributeTable: Sized {
-    fn with_locations_from(p: &Program) -> Result<Self, MissingAttributes>;
-    fn attributes(&self) -> impl Iterator<Item = &Attribute>;
-    fn enable_all(&self) {
-        self.attributes().for_each(Attribute::enable);
+    /// Looks up every attribute without failing.
+    fn lookup(ctx: &GlContext, p: &Program) -> TableLookup<Self>;
+    /// Fails only if a required attribute is missing.
+    fn with_locations_from(ctx: &GlContext, p: &Program) -> Result<Self, MissingAttributes> {
+        Self::lookup(ctx, p).required()
+    }
+    /// Fails if any attribute is missing, optional or not.
+    fn with_locations_from_strict(ctx: &GlContext, p: &Program) -> Result<Self, MissingAttributes> {
+        Self::lookup(ctx, p).strict()
+    }
+    fn fields() -> Vec<AttributeField>;
+    /// Every attribute alongside its name in the shader.
+    fn named_attributes(&self) -> impl Iterator<Item = (&'static str, &Attribute)>;
+    fn attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.named_attributes().map(|(_, a)| a)
+    }
+    fn attribute_named(&self, name: &str) -> Option<Attribute> {
+        self.named_attributes()
+            .find(|(n, _)| *n == name)
+            .map(|(_, a)| *a)
+    }
+    fn enable_all(&self, ctx: &GlContext) {
+        self.attributes().for_each(|a| a.enable(ctx));
     }
-    fn disable_all(&self) {
-        self.attributes().for_each(Attribute::disable);
+    fn disable_all(&self, ctx: &GlContext) {
+        self.attributes().for_each(|a| a.disable(ctx));
     }
 }
 
 #[macro_export]
 macro_rules! attribute_table {
-    ($sname:ident,$($lname:ident => $lstr:expr),*) => {
+    ($sname:ident,$($(#[$opt:ident])? $lname:ident => $lstr:expr),*) => {
         #[derive(Default,Clone,Copy,PartialEq,Eq)]
         pub struct $sname {
             $($lname: $crate::attribute::Attribute),*
         }
         impl $crate::attribute::AttributeTable for $sname {
-            fn with_locations_from(p: &$crate::program::Program) -> Result<Self,$crate::attribute::MissingAttributes> {
-                let to_check = [$($lstr),*];
-                let locations = [$(p.get_attrib_location($lstr)),*];
-                let errors: Vec<&'static str> = to_check.into_iter().zip(locations.iter()).filter_map(|(n,&l)| if l < 0 {Some(n)} else {None}).collect();
-                if !errors.is_empty() {
-                    return Err($crate::attribute::MissingAttributes(errors));
-                }
-                let mut locations_iter = locations.into_iter();
-                Ok($sname {
-                    $($lname: $crate::attribute::Attribute(locations_iter.next().unwrap() as u32)),*
+            fn lookup(
+                ctx: &$crate::GlContext,
+                p: &$crate::program::Program,
+            ) -> $crate::program::TableLookup<Self> {
+                $crate::program::lookup_locations(
+                    [$(($lstr, $crate::__table_entry_optional!($($opt)?))),*],
+                    |n| p.get_attrib_location(ctx, n),
+                ).map(|locations| {
+                    let mut locations_iter = locations.into_iter();
+                    $sname {
+                        $($lname: $crate::attribute::Attribute(locations_iter.next().unwrap() as u32)),*
+                    }
                 })
             }
-            fn attributes(&self) -> impl Iterator<Item=&$crate::attribute::Attribute> {
+            fn fields() -> Vec<$crate::attribute::AttributeField> {
+                vec![$($crate::attribute::AttributeField {
+                    name: $lstr,
+                    optional: $crate::__table_entry_optional!($($opt)?),
+                }),*]
+            }
+            fn named_attributes(&self) -> impl Iterator<Item=(&'static str, &$crate::attribute::Attribute)> {
                 [
-                    $(&self.$lname),*
+                    $(($lstr, &self.$lname)),*
                 ].into_iter()
             }
         }
     }
 }
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __table_entry_optional {
+    () => {
+        false
+    };
+    (optional) => {
+        true
+    };
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, GlCall};
+
+    attribute_table!(GridAttributes, pos => "pos", #[optional] color => "color");
+
+    #[test]
+    fn table_lookup() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.add_attribute("pos", gl::FLOAT_VEC2, 4));
+        let lookup = GridAttributes::lookup(&ctx, &Program::from(1));
+        assert!(lookup.missing_required.is_empty());
+        assert_eq!(lookup.missing_optional, ["color"]);
+        assert_eq!(lookup.table.pos.0, 4);
+        assert!(lookup.table.color.is_missing());
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::GetAttribLocation {
+                    program: 1,
+                    name: "pos".into(),
+                },
+                GlCall::GetAttribLocation {
+                    program: 1,
+                    name: "color".into(),
+                },
+            ]
+        );
+        let error = GridAttributes::with_locations_from_strict(&ctx, &Program::from(1))
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Missing Attributes: [color]");
+    }
+
+    #[test]
+    fn missing_attributes_are_no_ops() {
+        let ctx = mock::install();
+        mock::reset();
+        let table = GridAttributes {
+            pos: Attribute(4),
+            color: Attribute::MISSING,
+        };
+        table.enable_all(&ctx);
+        Attribute::MISSING.divisor(&ctx, 1);
+        table.disable_all(&ctx);
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::EnableVertexAttribArray(4),
+                GlCall::DisableVertexAttribArray(4),
+            ]
+        );
+    }
+}
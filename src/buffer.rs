@@ -1,67 +1,128 @@
+use std::{marker::PhantomData, ops::Deref};
+
+use bytemuck::Pod;
 use derive_more::{From, Into};
 use gl::types::{GLenum, GLsizei, GLuint};
 
-use crate::attribute::{Attribute, AttributeFormat};
+use crate::{
+    GlContext,
+    attribute::{Attribute, AttributeFormat, AttributeTable, MissingAttributes},
+    context::NotSend,
+    draw::{ElementsBufU16, ElementsBufU32},
+    errors::check_wrapper,
+    vertex::Vertex,
+};
 
 /// To create and delete buffers:
-/// ```rust
-/// use vita_gl_helpers::buffer::GenDelBuffersExt;
+/// ```rust,no_run
+/// use vita_gl_helpers::buffer::{Buffer, GenDelBuffersExt};
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
 /// let mut buffers = vec![Buffer::default();5];
-/// buffers.gen_buffers();
+/// buffers.gen_buffers(&ctx);
 /// // Do things with the buffers
-/// buffers.del_buffers();
+/// buffers.del_buffers(&ctx);
 /// ```
+/// Or use an [`OwnedBuffer`] to have it deleted on drop.
 #[derive(From, Into, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub struct Buffer(GLuint);
 
 impl Buffer {
-    pub fn bind(&self, target: impl Into<GLenum>) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind(&self, _ctx: &GlContext, target: impl Into<GLenum>) {
+        let target = target.into();
         unsafe {
-            gl::BindBuffer(target.into(), self.0);
+            gl::BindBuffer(target, self.0);
         }
+        check_wrapper!("Buffer::bind", target, self.0);
     }
+    #[cfg_attr(gl_check, track_caller)]
     pub fn bind_then<R>(
         &self,
+        ctx: &GlContext,
         target: impl Into<GLenum>,
         then: impl FnOnce(BoundBuffer) -> R,
     ) -> R {
-        let target = target.into();
-        self.bind(target);
-        then(BoundBuffer(target))
+        then(self.bound(ctx, target))
     }
-    pub fn data<T>(
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn data<T: Pod>(
         &self,
+        ctx: &GlContext,
         target: impl Into<GLenum>,
         data: impl AsRef<[T]>,
         usage: impl Into<GLenum>,
     ) {
-        self.bind_then(target, |b| b.data::<T>(data, usage));
+        self.bound(ctx, target).data::<T>(data, usage);
     }
+    #[cfg_attr(gl_check, track_caller)]
     pub fn bind_to(
         &self,
+        ctx: &GlContext,
         attribute: Attribute,
         format: AttributeFormat,
         stride: GLsizei,
         offset: usize,
     ) {
-        self.bind_then(gl::ARRAY_BUFFER, |b| {
-            b.bind_to(attribute, format, stride, offset)
-        })
+        self.bound(ctx, gl::ARRAY_BUFFER)
+            .bind_to(attribute, format, stride, offset)
+    }
+    /// Binds every field of an interleaved `V` to the attribute of the same name in `table`.
+    ///
+    /// Fields the table has no attribute for are skipped and reported in the error.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_vertices<V: Vertex>(
+        &self,
+        ctx: &GlContext,
+        table: &impl AttributeTable,
+    ) -> Result<(), MissingAttributes> {
+        self.bound(ctx, gl::ARRAY_BUFFER).bind_vertices::<V>(table)
+    }
+    /// Like [`bind_then`](Self::bind_then), but without a closure, so the `gl-check`
+    /// feature can report the caller of the method using it.
+    #[cfg_attr(gl_check, track_caller)]
+    fn bound(&self, ctx: &GlContext, target: impl Into<GLenum>) -> BoundBuffer {
+        let target = target.into();
+        self.bind(ctx, target);
+        BoundBuffer(target, PhantomData)
     }
 }
 
+/// A buffer just bound to a target. Only made by binding, which took a [`GlContext`],
+/// so its methods don't ask for one again.
 #[non_exhaustive]
-pub struct BoundBuffer(GLenum);
+pub struct BoundBuffer(GLenum, NotSend);
 
 impl BoundBuffer {
-    pub fn data<T>(&self, data: impl AsRef<[T]>, usage: impl Into<GLenum>) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn data<T: Pod>(&self, data: impl AsRef<[T]>, usage: impl Into<GLenum>) {
+        let data = data.as_ref();
+        let n_bytes = size_of_val(data);
+        let usage = usage.into();
+        unsafe {
+            gl::BufferData(self.0, n_bytes as _, data.as_ptr() as _, usage);
+        }
+        check_wrapper!("BoundBuffer::data", self.0, n_bytes, usage);
+    }
+    /// Allocates `n_bytes` of storage without uploading anything, orphaning any old storage.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn data_uninit(&self, n_bytes: usize, usage: impl Into<GLenum>) {
+        let usage = usage.into();
+        unsafe {
+            gl::BufferData(self.0, n_bytes as _, std::ptr::null(), usage);
+        }
+        check_wrapper!("BoundBuffer::data_uninit", self.0, n_bytes, usage);
+    }
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn sub_data<T: Pod>(&self, offset_bytes: usize, data: impl AsRef<[T]>) {
         let data = data.as_ref();
-        let n_bytes = size_of::<T>() * data.len();
+        let n_bytes = size_of_val(data);
         unsafe {
-            gl::BufferData(self.0, n_bytes as _, data.as_ptr() as _, usage.into());
+            gl::BufferSubData(self.0, offset_bytes as _, n_bytes as _, data.as_ptr() as _);
         }
+        check_wrapper!("BoundBuffer::sub_data", self.0, offset_bytes, n_bytes);
     }
+    #[cfg_attr(gl_check, track_caller)]
     pub fn bind_to(
         &self,
         attribute: Attribute,
@@ -71,21 +132,478 @@ impl BoundBuffer {
     ) {
         unsafe { attribute.pointer(format, stride, offset as _) }
     }
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_vertices<V: Vertex>(
+        &self,
+        table: &impl AttributeTable,
+    ) -> Result<(), MissingAttributes> {
+        let mut missing = Vec::new();
+        for field in V::FIELDS {
+            match table.attribute_named(field.name) {
+                Some(attribute) => {
+                    self.bind_to(attribute, field.format, size_of::<V>() as _, field.offset)
+                }
+                None => missing.push(field.name),
+            }
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingAttributes(missing))
+        }
+    }
 }
 
 pub trait GenDelBuffersExt {
-    fn gen_buffers(&mut self);
-    fn del_buffers(&mut self);
+    fn gen_buffers(&mut self, ctx: &GlContext);
+    fn del_buffers(&mut self, ctx: &GlContext);
 }
 
 impl<T: AsMut<[Buffer]>> GenDelBuffersExt for T {
-    fn gen_buffers(&mut self) {
+    #[cfg_attr(gl_check, track_caller)]
+    fn gen_buffers(&mut self, _ctx: &GlContext) {
         let as_mut = self.as_mut();
         unsafe { gl::GenBuffers(as_mut.len() as i32, as_mut.as_mut_ptr() as _) }
+        check_wrapper!("gen_buffers", as_mut.len());
     }
 
-    fn del_buffers(&mut self) {
+    #[cfg_attr(gl_check, track_caller)]
+    fn del_buffers(&mut self, _ctx: &GlContext) {
         let as_mut = self.as_mut();
         unsafe { gl::DeleteBuffers(as_mut.len() as i32, as_mut.as_mut_ptr() as _) }
+        check_wrapper!("del_buffers", as_mut.len());
+    }
+}
+
+/// A [`Buffer`] that is deleted when dropped. Derefs to the [`Buffer`] handle.
+///
+/// `!Send`, so it is dropped on the GL thread.
+#[derive(PartialEq, Eq)]
+pub struct OwnedBuffer(Buffer, NotSend);
+
+impl OwnedBuffer {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn generate(ctx: &GlContext) -> Self {
+        let mut buffer = [Buffer::default()];
+        buffer.gen_buffers(ctx);
+        OwnedBuffer(buffer[0], PhantomData)
+    }
+    /// Takes ownership of `buffer`, which will be deleted when this is dropped.
+    pub fn from_raw(_ctx: &GlContext, buffer: Buffer) -> Self {
+        OwnedBuffer(buffer, PhantomData)
+    }
+    /// Gives up ownership without deleting the buffer.
+    pub fn into_raw(self) -> Buffer {
+        let buffer = self.0;
+        std::mem::forget(self);
+        buffer
+    }
+}
+
+impl Deref for OwnedBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        &self.0
+    }
+}
+
+impl Drop for OwnedBuffer {
+    fn drop(&mut self) {
+        // Made with a context, and can't have left its thread since.
+        let ctx = unsafe { GlContext::assume_current() };
+        [self.0].del_buffers(&ctx);
+    }
+}
+
+/// A buffer that remembers its element type, length and usage.
+///
+/// ```rust,no_run
+/// use vita_gl_helpers::{buffer::TypedBuffer, draw::{Elements, Mode}};
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
+/// let indices = TypedBuffer::new(&ctx, gl::ELEMENT_ARRAY_BUFFER, &[0u16, 1, 2], gl::DYNAMIC_DRAW);
+/// indices.update_range(&ctx, 1, &[2, 1]);
+/// indices.elements().draw(&ctx, Mode::Triangles);
+/// ```
+pub struct TypedBuffer<T: Pod> {
+    buffer: OwnedBuffer,
+    target: GLenum,
+    len: usize,
+    usage: GLenum,
+    _marker: PhantomData<[T]>,
+}
+
+impl<T: Pod> TypedBuffer<T> {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn new(
+        ctx: &GlContext,
+        target: impl Into<GLenum>,
+        data: &[T],
+        usage: impl Into<GLenum>,
+    ) -> Self {
+        let buffer = TypedBuffer {
+            buffer: OwnedBuffer::generate(ctx),
+            target: target.into(),
+            len: data.len(),
+            usage: usage.into(),
+            _marker: PhantomData,
+        };
+        buffer.bound(ctx).data(data, buffer.usage);
+        buffer
+    }
+    /// Allocates room for `len` elements without uploading anything.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn with_len(
+        ctx: &GlContext,
+        target: impl Into<GLenum>,
+        len: usize,
+        usage: impl Into<GLenum>,
+    ) -> Self {
+        byte_len::<T>(len);
+        let buffer = TypedBuffer {
+            buffer: OwnedBuffer::generate(ctx),
+            target: target.into(),
+            len,
+            usage: usage.into(),
+            _marker: PhantomData,
+        };
+        buffer.orphan(ctx);
+        buffer
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn target(&self) -> GLenum {
+        self.target
+    }
+    pub fn usage(&self) -> GLenum {
+        self.usage
+    }
+    pub fn buffer(&self) -> Buffer {
+        *self.buffer
+    }
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_then<R>(&self, ctx: &GlContext, then: impl FnOnce(BoundBuffer) -> R) -> R {
+        self.buffer.bind_then(ctx, self.target, then)
+    }
+    #[cfg_attr(gl_check, track_caller)]
+    fn bound(&self, ctx: &GlContext) -> BoundBuffer {
+        self.buffer.bound(ctx, self.target)
+    }
+    /// Overwrites the elements starting at `offset` with `data`.
+    ///
+    /// Panics if that would run past the end of the buffer.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn update_range(&self, ctx: &GlContext, offset: usize, data: &[T]) {
+        match offset.checked_add(data.len()) {
+            Some(end) if end <= self.len => {}
+            Some(end) => panic!(
+                "update_range({offset}..{end}) out of bounds for TypedBuffer of length {}",
+                self.len
+            ),
+            None => panic!(
+                "update_range({offset}..{offset}+{}) overflows usize",
+                data.len()
+            ),
+        }
+        self.bound(ctx).sub_data(offset * size_of::<T>(), data);
+    }
+    /// Replaces the whole contents, changing the length to match `data`.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn set_data(&mut self, ctx: &GlContext, data: &[T]) {
+        self.len = data.len();
+        self.bound(ctx).data(data, self.usage);
+    }
+    /// Reallocates room for `len` elements. The contents are undefined afterwards.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn resize(&mut self, ctx: &GlContext, len: usize) {
+        byte_len::<T>(len);
+        self.len = len;
+        self.orphan(ctx);
+    }
+    /// Swaps in fresh storage of the same size, so the GPU can keep reading the old contents
+    /// while new ones are written. The contents are undefined afterwards.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn orphan(&self, ctx: &GlContext) {
+        self.bound(ctx)
+            .data_uninit(byte_len::<T>(self.len), self.usage);
+    }
+    /// Points `attribute` at this buffer, assuming tightly packed elements.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_to(&self, ctx: &GlContext, attribute: Attribute, format: AttributeFormat) {
+        self.buffer.bind_to(ctx, attribute, format, 0, 0);
+    }
+    pub fn into_owned_buffer(self) -> OwnedBuffer {
+        self.buffer
+    }
+}
+
+/// The size in bytes of `len` elements of `T`, panicking if GL couldn't be asked for it.
+fn byte_len<T>(len: usize) -> usize {
+    len.checked_mul(size_of::<T>())
+        .filter(|&n_bytes| n_bytes <= isize::MAX as usize)
+        .unwrap_or_else(|| {
+            panic!(
+                "TypedBuffer of {len} elements of {} bytes is too large",
+                size_of::<T>()
+            )
+        })
+}
+
+impl<V: Vertex> TypedBuffer<V> {
+    /// See [`Buffer::bind_vertices`].
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_vertices(
+        &self,
+        ctx: &GlContext,
+        table: &impl AttributeTable,
+    ) -> Result<(), MissingAttributes> {
+        self.buffer.bind_vertices::<V>(ctx, table)
+    }
+}
+
+/// `len` as an element count, which draws take as a `GLsizei`.
+fn element_count(len: usize) -> u32 {
+    match GLsizei::try_from(len) {
+        Ok(count) => count as u32,
+        Err(_) => panic!("{len} elements are too many to draw at once"),
+    }
+}
+
+impl TypedBuffer<u16> {
+    pub fn elements(&self) -> ElementsBufU16 {
+        ElementsBufU16 {
+            indices: self.buffer(),
+            len: element_count(self.len),
+        }
+    }
+}
+
+impl TypedBuffer<u32> {
+    pub fn elements(&self) -> ElementsBufU32 {
+        ElementsBufU32 {
+            indices: self.buffer(),
+            len: element_count(self.len),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use bytemuck::Zeroable;
+
+    use super::*;
+    use crate::{
+        attribute::AttributeSize,
+        attribute_table,
+        mock::{self, GlCall},
+        program::Program,
+        vertex::{VertexAttribute, VertexField},
+    };
+
+    #[derive(Clone, Copy, Pod, Zeroable)]
+    #[repr(C)]
+    struct ColorVertex {
+        pos: [f32; 2],
+        color: [u8; 4],
+    }
+
+    impl Vertex for ColorVertex {
+        const FIELDS: &'static [VertexField] = &[
+            VertexField {
+                name: "aPos",
+                format: <[f32; 2]>::FORMAT,
+                offset: 0,
+            },
+            VertexField {
+                name: "aColor",
+                format: <[u8; 4]>::FORMAT,
+                offset: 8,
+            },
+        ];
+    }
+
+    attribute_table!(ColorAttributes, pos => "aPos", #[optional] color => "aColor");
+    attribute_table!(PosAttributes, pos => "aPos");
+
+    #[test]
+    fn typed_buffer_calls() {
+        let ctx = mock::install();
+        mock::reset();
+        let buffer = TypedBuffer::new(&ctx, gl::ARRAY_BUFFER, &[1u16, 2, 3], gl::STATIC_DRAW);
+        buffer.update_range(&ctx, 1, &[4]);
+        buffer.orphan(&ctx);
+        drop(buffer);
+        let bind = GlCall::BindBuffer {
+            target: gl::ARRAY_BUFFER,
+            buffer: 1,
+        };
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::GenBuffers(vec![1]),
+                bind.clone(),
+                GlCall::BufferData {
+                    target: gl::ARRAY_BUFFER,
+                    data: Some(vec![1, 0, 2, 0, 3, 0]),
+                    size: 6,
+                    usage: gl::STATIC_DRAW,
+                },
+                bind.clone(),
+                GlCall::BufferSubData {
+                    target: gl::ARRAY_BUFFER,
+                    offset: 2,
+                    data: vec![4, 0],
+                },
+                bind,
+                GlCall::BufferData {
+                    target: gl::ARRAY_BUFFER,
+                    data: None,
+                    size: 6,
+                    usage: gl::STATIC_DRAW,
+                },
+                GlCall::DeleteBuffers(vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn into_raw_keeps_buffer() {
+        let ctx = mock::install();
+        mock::reset();
+        let buffer = OwnedBuffer::generate(&ctx).into_raw();
+        assert_eq!(mock::take_calls(), [GlCall::GenBuffers(vec![1])]);
+        drop(OwnedBuffer::from_raw(&ctx, buffer));
+        assert_eq!(mock::take_calls(), [GlCall::DeleteBuffers(vec![1])]);
+    }
+
+    #[test]
+    fn bind_vertices_interleaved() {
+        let ctx = mock::install();
+        mock::reset();
+        let table = ColorAttributes {
+            pos: Attribute(2),
+            color: Attribute(5),
+        };
+        assert!(
+            Buffer::from(7)
+                .bind_vertices::<ColorVertex>(&ctx, &table)
+                .is_ok()
+        );
+        let pointer = |index, size: AttributeSize, type_, pointer| GlCall::VertexAttribPointer {
+            index,
+            size: size as _,
+            type_,
+            normalized: false,
+            stride: 12,
+            pointer,
+        };
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::BindBuffer {
+                    target: gl::ARRAY_BUFFER,
+                    buffer: 7,
+                },
+                pointer(2, AttributeSize::TWO, gl::FLOAT, 0),
+                pointer(5, AttributeSize::FOUR, gl::UNSIGNED_BYTE, 8),
+            ]
+        );
+    }
+
+    #[cfg(gl_check)]
+    #[test]
+    fn bind_vertices_reports_caller() {
+        let ctx = crate::errors::tests::install();
+        let table = ColorAttributes {
+            pos: Attribute(2),
+            color: Attribute(5),
+        };
+        let buffer =
+            TypedBuffer::<ColorVertex>::with_len(&ctx, gl::ARRAY_BUFFER, 1, gl::STATIC_DRAW);
+        mock::with(|m| m.errors.push_back(gl::INVALID_VALUE));
+        let buffer_line = line!() + 1;
+        let _ = Buffer::from(7).bind_vertices::<ColorVertex>(&ctx, &table);
+        mock::with(|m| m.errors.push_back(gl::INVALID_VALUE));
+        let typed_line = line!() + 1;
+        let _ = buffer.bind_vertices(&ctx, &table);
+        let reports = crate::errors::tests::take_reports();
+        let lines: Vec<(&str, u32)> = reports.iter().map(|r| (r.file, r.line)).collect();
+        assert_eq!(lines, [(file!(), buffer_line), (file!(), typed_line)]);
+    }
+
+    #[test]
+    fn bind_vertices_skips_missing() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.add_attribute("aPos", gl::FLOAT_VEC2, 0));
+        let table = ColorAttributes::lookup(&ctx, &Program::from(1)).table;
+        assert!(table.color.is_missing());
+        mock::take_calls();
+        // A missing optional attribute is in the table, so nothing is reported.
+        assert!(
+            Buffer::from(7)
+                .bind_vertices::<ColorVertex>(&ctx, &table)
+                .is_ok()
+        );
+        let pointers = mock::take_calls()
+            .into_iter()
+            .filter(|call| matches!(call, GlCall::VertexAttribPointer { .. }))
+            .count();
+        assert_eq!(pointers, 1);
+        // A table without the field at all is.
+        let table = PosAttributes { pos: Attribute(0) };
+        let error = Buffer::from(7)
+            .bind_vertices::<ColorVertex>(&ctx, &table)
+            .unwrap_err();
+        assert_eq!(error.0, ["aColor"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "update_range(3..5) out of bounds for TypedBuffer of length 4")]
+    fn update_range_past_end() {
+        let ctx = mock::install();
+        let buffer = TypedBuffer::new(&ctx, gl::ARRAY_BUFFER, &[0u16; 4], gl::STATIC_DRAW);
+        buffer.update_range(&ctx, 3, &[1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "2147483648 elements are too many to draw at once")]
+    fn elements_overflow() {
+        let ctx = mock::install();
+        let mut buffer =
+            TypedBuffer::new(&ctx, gl::ELEMENT_ARRAY_BUFFER, &[0u32; 4], gl::STATIC_DRAW);
+        assert_eq!(buffer.elements().len, 4);
+        // Only the length matters here; a buffer this size won't fit in memory.
+        buffer.len = 1 << 31;
+        buffer.elements();
+    }
+
+    #[test]
+    #[should_panic(expected = "overflows usize")]
+    fn update_range_overflow() {
+        let ctx = mock::install();
+        let buffer = TypedBuffer::new(&ctx, gl::ARRAY_BUFFER, &[0u16; 4], gl::STATIC_DRAW);
+        buffer.update_range(&ctx, usize::MAX, &[1]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "TypedBuffer of 4611686018427387904 elements of 4 bytes is too large"
+    )]
+    fn with_len_overflow() {
+        let ctx = mock::install();
+        TypedBuffer::<u32>::with_len(&ctx, gl::ARRAY_BUFFER, 1 << 62, gl::STATIC_DRAW);
+    }
+
+    #[test]
+    #[should_panic(expected = "too large")]
+    fn resize_overflow() {
+        let ctx = mock::install();
+        let mut buffer = TypedBuffer::<u64>::with_len(&ctx, gl::ARRAY_BUFFER, 4, gl::STATIC_DRAW);
+        buffer.resize(&ctx, usize::MAX / 4);
     }
 }
@@ -0,0 +1,355 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use derive_more::From;
+
+use crate::{
+    GlContext, RuntimeShaderCompilerSettings,
+    program::{Program, ProgramError, link_program},
+    shader::{OwnedShader, ShaderError, ShaderStage, load_shader},
+};
+
+/// Bump whenever the file layout or what goes into the key changes.
+const CACHE_VERSION: u32 = 2;
+const CACHE_MAGIC: [u8; 4] = *b"VGLP";
+/// Magic, version, settings hash, source digest and binary format.
+const HEADER_LEN: usize = 4 + 4 + 8 + 16 + 4;
+
+#[derive(Debug, From)]
+pub enum CacheError {
+    Shader(ShaderError),
+    Program(ProgramError),
+}
+
+impl std::fmt::Display for CacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheError::Shader(e) => write!(f, "{e}"),
+            CacheError::Program(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// Keeps linked program binaries on disk so later boots can skip the runtime shader compiler.
+///
+/// Entries are keyed by both sources and the [`RuntimeShaderCompilerSettings`], so
+/// changing either just misses the cache. The key only names the file: each entry also
+/// carries a digest of the sources, so sources that share a key can't load each other's
+/// binary. Needs `glGetProgramBinary` and `glProgramBinary`; where vitaGL doesn't provide
+/// them every program is compiled as usual.
+/// ```rust,no_run
+/// use vita_gl_helpers::{RuntimeShaderCompilerSettings, cache::ProgramCache};
+/// let settings = RuntimeShaderCompilerSettings::default();
+/// let cache = ProgramCache::new("ux0:data/ABCD12345/shadercache", settings);
+/// let ctx = vita_gl_helpers::initialise_extended(settings, Default::default()).unwrap();
+/// # let (vertex_source, fragment_source) = ("", "");
+/// let program = cache.load_or_link(&ctx, vertex_source, fragment_source).unwrap();
+/// ```
+pub struct ProgramCache {
+    dir: PathBuf,
+    settings: RuntimeShaderCompilerSettings,
+}
+
+impl ProgramCache {
+    /// `settings` must be the ones the runtime compiler was set up with.
+    pub fn new(dir: impl Into<PathBuf>, settings: RuntimeShaderCompilerSettings) -> Self {
+        ProgramCache {
+            dir: dir.into(),
+            settings,
+        }
+    }
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// Loads the program from the cache, or compiles and links it and stores the result.
+    ///
+    /// Failing to store the binary is not an error, it just gets compiled again next time.
+    pub fn load_or_link(
+        &self,
+        ctx: &GlContext,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Result<Program, CacheError> {
+        if let Some(program) = self.load(ctx, vertex_source, fragment_source) {
+            return Ok(program);
+        }
+        let vert = load_shader(ctx, vertex_source, ShaderStage::Vertex)?;
+        let vert = OwnedShader::from_raw(ctx, vert);
+        let frag = load_shader(ctx, fragment_source, ShaderStage::Fragment)?;
+        let frag = OwnedShader::from_raw(ctx, frag);
+        let program = link_program(ctx, *vert, *frag)?;
+        let _ = self.store(ctx, vertex_source, fragment_source, program);
+        Ok(program)
+    }
+    /// The cached program for these sources, if there is one and GL accepts it.
+    /// Entries GL rejects are removed.
+    pub fn load(
+        &self,
+        _ctx: &GlContext,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Option<Program> {
+        if !gl::ProgramBinary::is_loaded() {
+            return None;
+        }
+        let path = self.path(vertex_source, fragment_source);
+        let file = std::fs::read(&path).ok()?;
+        let Some((format, binary)) = self.parse(&file, vertex_source, fragment_source) else {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        };
+        let program = unsafe { gl::CreateProgram() };
+        if program == 0 {
+            return None;
+        }
+        unsafe {
+            gl::ProgramBinary(program, format, binary.as_ptr() as _, binary.len() as _);
+            let program = Program::from(program);
+            if program.get_iv(gl::LINK_STATUS) == 0 {
+                program.delete();
+                let _ = std::fs::remove_file(&path);
+                return None;
+            }
+            Some(program)
+        }
+    }
+    /// Writes the binary of `program`, which was linked from these sources.
+    pub fn store(
+        &self,
+        _ctx: &GlContext,
+        vertex_source: &str,
+        fragment_source: &str,
+        program: Program,
+    ) -> io::Result<()> {
+        if !gl::GetProgramBinary::is_loaded() {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let len = unsafe { program.get_iv(gl::PROGRAM_BINARY_LENGTH) };
+        if len <= 0 {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        let mut binary = vec![0u8; len as usize];
+        let (mut written, mut format) = (0, 0);
+        unsafe {
+            gl::GetProgramBinary(
+                program.into(),
+                len,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as _,
+            )
+        };
+        binary.truncate(written.max(0) as usize);
+        let mut file = Vec::with_capacity(HEADER_LEN + binary.len());
+        file.extend_from_slice(&CACHE_MAGIC);
+        file.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        file.extend_from_slice(&self.settings_hash().to_le_bytes());
+        file.extend_from_slice(&source_digest(vertex_source, fragment_source).to_le_bytes());
+        file.extend_from_slice(&format.to_le_bytes());
+        file.extend_from_slice(&binary);
+        std::fs::create_dir_all(&self.dir)?;
+        // Written aside and renamed, so a power cut can't leave half an entry behind.
+        let path = self.path(vertex_source, fragment_source);
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, file)?;
+        std::fs::rename(partial, path)
+    }
+    /// Removes every cached program.
+    pub fn clear(&self) -> io::Result<()> {
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "bin" || e == "part") {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+    fn path(&self, vertex_source: &str, fragment_source: &str) -> PathBuf {
+        let mut hash = Fnv1a::new();
+        hash.write(&CACHE_VERSION.to_le_bytes());
+        hash.write(&self.settings_hash().to_le_bytes());
+        for source in [vertex_source, fragment_source] {
+            hash.write(&(source.len() as u64).to_le_bytes());
+            hash.write(source.as_bytes());
+        }
+        self.dir.join(format!("{:016x}.bin", hash.0))
+    }
+    fn settings_hash(&self) -> u64 {
+        let s = &self.settings;
+        let mut hash = Fnv1a::new();
+        for setting in [
+            s.opt_level,
+            s.use_fastmath,
+            s.use_fastprecision,
+            s.use_fastint,
+        ] {
+            hash.write(&setting.to_le_bytes());
+        }
+        hash.0
+    }
+    /// The binary format and binary, if the header matches this crate, the settings and
+    /// the sources.
+    fn parse<'a>(
+        &self,
+        file: &'a [u8],
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Option<(gl::types::GLenum, &'a [u8])> {
+        let (header, binary) = file.split_at_checked(HEADER_LEN)?;
+        let (magic, rest) = header.split_at(4);
+        let (version, rest) = rest.split_at(4);
+        let (settings, rest) = rest.split_at(8);
+        let (digest, format) = rest.split_at(16);
+        let matches = magic == CACHE_MAGIC
+            && version == CACHE_VERSION.to_le_bytes()
+            && settings == self.settings_hash().to_le_bytes()
+            && digest == source_digest(vertex_source, fragment_source).to_le_bytes();
+        matches.then(|| (u32::from_le_bytes(format.try_into().unwrap()), binary))
+    }
+}
+
+/// 128-bit FNV-1a of both sources, checked on load in case two share a file name.
+fn source_digest(vertex_source: &str, fragment_source: &str) -> u128 {
+    let mut digest = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d_u128;
+    for source in [vertex_source, fragment_source] {
+        let len = (source.len() as u64).to_le_bytes();
+        for &byte in len.iter().chain(source.as_bytes()) {
+            digest ^= byte as u128;
+            digest = digest.wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+        }
+    }
+    digest
+}
+
+/// FNV-1a, which unlike `std`'s hasher gives the same key on every boot and toolchain.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{self, GlCall},
+        preprocess::tests::TempDir,
+    };
+
+    fn cache(dir: &TempDir) -> ProgramCache {
+        ProgramCache::new(&dir.0, RuntimeShaderCompilerSettings::default())
+    }
+
+    /// Stores a program linked from `vertex` and `frag` with binary `binary`.
+    fn store(cache: &ProgramCache, vertex: &str, frag: &str, binary: &[u8]) {
+        let ctx = mock::install();
+        mock::with(|m| m.program_binary = binary.to_vec());
+        cache.store(&ctx, vertex, frag, Program::from(1)).unwrap();
+    }
+
+    fn loaded_binary(cache: &ProgramCache, vertex: &str, frag: &str) -> Option<Vec<u8>> {
+        let ctx = mock::install();
+        mock::take_calls();
+        cache.load(&ctx, vertex, frag)?;
+        mock::take_calls().into_iter().find_map(|call| match call {
+            GlCall::ProgramBinary { binary, .. } => Some(binary),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = TempDir::new(&[]);
+        let cache = cache(&dir);
+        mock::install();
+        mock::reset();
+        store(&cache, "vertex", "fragment", b"binary");
+        assert_eq!(
+            loaded_binary(&cache, "vertex", "fragment").as_deref(),
+            Some(&b"binary"[..])
+        );
+        assert_eq!(loaded_binary(&cache, "vertex", "other"), None);
+    }
+
+    #[test]
+    fn other_sources_under_the_same_key_miss() {
+        let dir = TempDir::new(&[]);
+        let cache = cache(&dir);
+        mock::install();
+        mock::reset();
+        store(&cache, "vertex", "fragment", b"binary");
+        // As if "vertex" and "other" hashed to the same file name.
+        let path = cache.path("vertex", "other");
+        std::fs::rename(cache.path("vertex", "fragment"), &path).unwrap();
+        assert_eq!(loaded_binary(&cache, "vertex", "other"), None);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn old_versions_miss() {
+        let dir = TempDir::new(&[]);
+        let cache = cache(&dir);
+        mock::install();
+        mock::reset();
+        store(&cache, "vertex", "fragment", b"binary");
+        let path = cache.path("vertex", "fragment");
+        let mut file = std::fs::read(&path).unwrap();
+        file[4..8].copy_from_slice(&(CACHE_VERSION - 1).to_le_bytes());
+        std::fs::write(&path, file).unwrap();
+        assert_eq!(loaded_binary(&cache, "vertex", "fragment"), None);
+    }
+
+    #[test]
+    fn other_settings_miss() {
+        let dir = TempDir::new(&[]);
+        let cache = cache(&dir);
+        mock::install();
+        mock::reset();
+        store(&cache, "vertex", "fragment", b"binary");
+        let stored = cache.path("vertex", "fragment");
+        let defaults = RuntimeShaderCompilerSettings::default();
+        for settings in [
+            RuntimeShaderCompilerSettings {
+                opt_level: 0,
+                ..defaults
+            },
+            RuntimeShaderCompilerSettings {
+                use_fastmath: 0,
+                ..defaults
+            },
+            RuntimeShaderCompilerSettings {
+                use_fastprecision: 1,
+                ..defaults
+            },
+            RuntimeShaderCompilerSettings {
+                use_fastint: 0,
+                ..defaults
+            },
+        ] {
+            let other = ProgramCache::new(&dir.0, settings);
+            let path = other.path("vertex", "fragment");
+            assert_ne!(path, stored);
+            assert_eq!(loaded_binary(&other, "vertex", "fragment"), None);
+            // As if the other settings gave the same file name, the header still differs.
+            std::fs::copy(&stored, &path).unwrap();
+            assert_eq!(loaded_binary(&other, "vertex", "fragment"), None);
+            assert!(!path.exists());
+        }
+        assert_eq!(
+            loaded_binary(&cache, "vertex", "fragment").as_deref(),
+            Some(&b"binary"[..])
+        );
+    }
+}
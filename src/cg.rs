@@ -0,0 +1,199 @@
+//! Checks attribute and uniform tables against Cg sources on the host, long before
+//! they reach a console. The parser itself is the `vita_gl_helpers_cg` crate, re-exported here.
+//! ```rust
+//! use vita_gl_helpers::{
+//!     attribute::{Attribute, AttributeTable},
+//!     cg::{self, CgProgram},
+//!     uniforms::{Uniform3fv, UniformMatrix4fv, UniformTable},
+//! };
+//! #[derive(AttributeTable)]
+//! struct Attributes {
+//!     #[gl(name = "aPos")]
+//!     pos: Attribute,
+//! }
+//! #[derive(UniformTable)]
+//! struct Uniforms {
+//!     mvp: UniformMatrix4fv,
+//!     #[gl(len = 4)]
+//!     lights: Uniform3fv,
+//! }
+//! let vertex = CgProgram::parse(
+//!     "
+//!     uniform float4x4 mvp;
+//!     uniform float3 lights[2];
+//!     void main(float3 aPos, float4 out gl_Position : POSITION) {
+//!         gl_Position = mul(mvp, float4(aPos + lights[0] + lights[1], 1.0));
+//!     }
+//!     ",
+//! )
+//! .unwrap();
+//! assert!(cg::check_attribute_table::<Attributes>(&vertex).is_ok());
+//! assert_eq!(
+//!     cg::check_uniform_table::<Uniforms>(&[&vertex]).unwrap_err().to_string(),
+//!     "Mismatched Uniforms: [lights: expected float3 or bool3 with length 4, found float3 with length 2]"
+//! );
+//! ```
+//! With the `derive` feature, [`cg_tables!`] goes further and generates the tables from the
+//! shaders at compile time, so they can't disagree:
+//! ```rust
+//! use vita_gl_helpers::{attribute::AttributeTable, cg::cg_tables};
+//! cg_tables!(
+//!     "examples/shaders/triangle_vert.cg",
+//!     "examples/shaders/triangle_frag.cg",
+//!     attributes = TriangleAttributes,
+//!     uniforms = TriangleUniforms,
+//! );
+//! let names: Vec<_> = TriangleAttributes::fields().iter().map(|f| f.name).collect();
+//! assert_eq!(names, ["aPos", "aColor"]);
+//! ```
+
+pub use vita_gl_helpers_cg::*;
+#[cfg(feature = "derive")]
+pub use vita_gl_helpers_derive::cg_tables;
+
+use crate::{
+    attribute::{AttributeTable, MissingAttributes},
+    reflect::{ActiveVariable, GlType},
+    shader::ShaderStage,
+    uniforms::{UniformTable, UniformTableError, field_base_name, mismatched_fields},
+};
+
+/// `half` and `fixed` are treated as `float`. Types GL has no enum for, like structs, become `Other(0)`.
+impl From<&CgType> for GlType {
+    fn from(value: &CgType) -> Self {
+        let matrix = |n| match n {
+            2 => gl::FLOAT_MAT2,
+            3 => gl::FLOAT_MAT3,
+            4 => gl::FLOAT_MAT4,
+            _ => 0,
+        };
+        let gl_type = match value {
+            CgType::Scalar(scalar) => scalar_type(*scalar, 1),
+            CgType::Vector(scalar, n) => scalar_type(*scalar, *n),
+            CgType::Matrix(Scalar::Int | Scalar::Bool, ..) => 0,
+            CgType::Matrix(_, rows, columns) if rows == columns => matrix(*rows),
+            CgType::Matrix(_, rows, columns) => match (rows, columns) {
+                (2, 3) => gl::FLOAT_MAT2x3,
+                (2, 4) => gl::FLOAT_MAT2x4,
+                (3, 2) => gl::FLOAT_MAT3x2,
+                (3, 4) => gl::FLOAT_MAT3x4,
+                (4, 2) => gl::FLOAT_MAT4x2,
+                (4, 3) => gl::FLOAT_MAT4x3,
+                _ => 0,
+            },
+            CgType::Sampler2D => gl::SAMPLER_2D,
+            CgType::SamplerCube => gl::SAMPLER_CUBE,
+            CgType::Other(_) => 0,
+        };
+        gl_type.into()
+    }
+}
+
+fn scalar_type(scalar: Scalar, n: u8) -> gl::types::GLenum {
+    let types = match scalar {
+        Scalar::Float | Scalar::Half | Scalar::Fixed => {
+            [gl::FLOAT, gl::FLOAT_VEC2, gl::FLOAT_VEC3, gl::FLOAT_VEC4]
+        }
+        Scalar::Int => [gl::INT, gl::INT_VEC2, gl::INT_VEC3, gl::INT_VEC4],
+        Scalar::Bool => [gl::BOOL, gl::BOOL_VEC2, gl::BOOL_VEC3, gl::BOOL_VEC4],
+    };
+    types[n as usize - 1]
+}
+
+/// How GL would report `variable` once linked, without a location.
+impl From<&CgVariable> for ActiveVariable {
+    fn from(variable: &CgVariable) -> Self {
+        ActiveVariable {
+            name: variable.name.clone(),
+            type_: (&variable.type_).into(),
+            size: variable.array_len.map_or(1, |len| len as _),
+            location: -1,
+        }
+    }
+}
+
+impl From<glsl::Stage> for ShaderStage {
+    fn from(stage: glsl::Stage) -> Self {
+        match stage {
+            glsl::Stage::Vertex => ShaderStage::Vertex,
+            glsl::Stage::Fragment => ShaderStage::Fragment,
+        }
+    }
+}
+
+/// Checks that every required field of `T` is an attribute of the vertex shader.
+pub fn check_attribute_table<T: AttributeTable>(
+    vertex: &CgProgram,
+) -> Result<(), MissingAttributes> {
+    let missing: Vec<&'static str> = T::fields()
+        .into_iter()
+        .filter(|field| !field.optional && !vertex.attributes().any(|v| v.name == field.name))
+        .map(|field| field.name)
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(missing.into())
+    }
+}
+
+/// Checks the fields of `T` against the uniforms of `shaders`, usually the vertex and
+/// fragment shader of one program, like [`UniformTable::with_locations_checked`] does.
+pub fn check_uniform_table<T: UniformTable>(
+    shaders: &[&CgProgram],
+) -> Result<(), UniformTableError> {
+    let declared: Vec<ActiveVariable> = shaders
+        .iter()
+        .flat_map(|shader| shader.uniforms())
+        .map(ActiveVariable::from)
+        .collect();
+    let fields = T::fields();
+    let missing: Vec<&'static str> = fields
+        .iter()
+        .filter(|field| {
+            !field.optional
+                && !declared
+                    .iter()
+                    .any(|u| u.base_name() == field_base_name(field))
+        })
+        .map(|field| field.name)
+        .collect();
+    let mismatched = mismatched_fields(fields, &declared);
+    if missing.is_empty() && mismatched.is_empty() {
+        Ok(())
+    } else {
+        Err(UniformTableError {
+            missing,
+            mismatched,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_matrices() {
+        let gl_type = |name| GlType::from(&CgType::parse(name));
+        assert_eq!(gl_type("float2x2"), GlType::FloatMat2);
+        assert_eq!(gl_type("half3x3"), GlType::FloatMat3);
+        assert_eq!(gl_type("float4x4"), GlType::FloatMat4);
+        assert_eq!(gl_type("float1x1"), GlType::Other(0));
+        assert_eq!(gl_type("float1x4"), GlType::Other(0));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn cg_tables_skip_system_values() {
+        use crate::uniforms::UniformTable;
+        cg_tables!(
+            "examples/instanced_colorful_grid/src/vert.cg",
+            "examples/instanced_colorful_grid/src/frag.cg",
+        );
+        let names: Vec<_> = Attributes::fields().iter().map(|f| f.name).collect();
+        assert_eq!(names, ["pos", "color_top", "color_bottom"]);
+        let names: Vec<_> = Uniforms::fields().iter().map(|f| f.name).collect();
+        assert_eq!(names, ["rect_dim"]);
+    }
+}
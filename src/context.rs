@@ -0,0 +1,36 @@
+use std::marker::PhantomData;
+
+/// Proof that vitaGL has been initialised on this thread, returned by
+/// [`initialise_extended`](crate::initialise_extended) and friends.
+///
+/// Every safe wrapper that calls GL takes a `&GlContext`, so calling GL before
+/// initialising, or from another thread, doesn't compile:
+/// ```rust,compile_fail
+/// # use vita_gl_helpers::GlContext;
+/// fn send(_: impl Send) {}
+/// send(unsafe { GlContext::assume_current() });
+/// ```
+/// `unsafe` functions leave this to their callers, as part of their safety contract.
+///
+/// Owned handles like [`OwnedBuffer`](crate::buffer::OwnedBuffer) are `!Send` too, since
+/// dropping them calls GL.
+#[derive(Debug)]
+pub struct GlContext {
+    _not_send: NotSend,
+}
+
+impl GlContext {
+    /// Makes a token without initialising anything, e.g. for code handed GL by something
+    /// else.
+    ///
+    /// # Safety
+    /// vitaGL must have been initialised, and GL loaded, on the calling thread.
+    pub unsafe fn assume_current() -> Self {
+        GlContext {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Makes a type `!Send` and `!Sync`, to keep it on the GL thread.
+pub(crate) type NotSend = PhantomData<*const ()>;
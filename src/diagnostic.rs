@@ -0,0 +1,437 @@
+use crate::{preprocess::LineMap, shader::ShaderStage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "info",
+            }
+        )
+    }
+}
+
+/// One message from the shader compiler's info log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticEntry {
+    /// 1-based line in the compiled source, if the compiler gave one.
+    pub line: Option<u32>,
+    /// 1-based column, if the compiler gave one. vitaGL's own log never does.
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// The file and line `line` came from, once [remapped](ShaderDiagnostic::remap).
+    pub origin: Option<(String, u32)>,
+}
+
+impl DiagnosticEntry {
+    /// Parses one line of an info log. Understands vitaGL's `E] message on line 3`, as well as
+    /// `file(3,5): error: message`, `file:3:5: error: message` and `(3, 5): error message`.
+    pub fn parse(log_line: &str) -> Option<DiagnosticEntry> {
+        let log_line = log_line.trim();
+        if log_line.is_empty() {
+            return None;
+        }
+        Some(
+            Self::parse_vitagl(log_line)
+                .or_else(|| Self::parse_located(log_line))
+                .unwrap_or_else(|| DiagnosticEntry {
+                    line: None,
+                    column: None,
+                    severity: Self::guess_severity(log_line),
+                    message: log_line.to_string(),
+                    origin: None,
+                }),
+        )
+    }
+
+    fn parse_vitagl(log_line: &str) -> Option<DiagnosticEntry> {
+        let (level, rest) = log_line.split_once("] ")?;
+        let severity = match level {
+            "E" => Severity::Error,
+            "W" => Severity::Warning,
+            "I" => Severity::Info,
+            _ => return None,
+        };
+        let (message, line) = match rest.rsplit_once(" on line ") {
+            Some((message, line)) => (message, line.trim().parse().ok()),
+            None => (rest, None),
+        };
+        Some(DiagnosticEntry {
+            line: line.filter(|&l| l > 0),
+            column: None,
+            severity,
+            message: message.to_string(),
+            origin: None,
+        })
+    }
+
+    fn parse_located(log_line: &str) -> Option<DiagnosticEntry> {
+        let (line, column, rest) = Self::split_paren_location(log_line)
+            .or_else(|| Self::split_colon_location(log_line))?;
+        let rest = rest.trim();
+        let (severity, message) =
+            Self::split_severity(rest).unwrap_or((Self::guess_severity(rest), rest));
+        Some(DiagnosticEntry {
+            line: Some(line).filter(|&l| l > 0),
+            column: column.filter(|&c| c > 0),
+            severity,
+            message: message.to_string(),
+            origin: None,
+        })
+    }
+
+    /// `file(3,5): rest` or `(3, 5): rest`
+    fn split_paren_location(log_line: &str) -> Option<(u32, Option<u32>, &str)> {
+        let open = log_line.find('(')?;
+        let close = open + log_line[open..].find("):")?;
+        let mut numbers = log_line[open + 1..close]
+            .split(',')
+            .map(|n| n.trim().parse());
+        let line = numbers.next()?.ok()?;
+        let column = numbers.next().and_then(Result::ok);
+        Some((line, column, &log_line[close + 2..]))
+    }
+
+    /// `file:3:5: rest` or `file:3: rest`
+    fn split_colon_location(log_line: &str) -> Option<(u32, Option<u32>, &str)> {
+        let (_file, rest) = log_line.split_once(':')?;
+        let (line, rest) = rest.split_once(':')?;
+        let line = line.trim().parse().ok()?;
+        match rest.split_once(':') {
+            Some((column, after)) if column.trim().parse::<u32>().is_ok() => {
+                Some((line, column.trim().parse().ok(), after))
+            }
+            _ => Some((line, None, rest)),
+        }
+    }
+
+    /// `error: message` or `error C1008: message`
+    fn split_severity(rest: &str) -> Option<(Severity, &str)> {
+        let (severity, after) = [
+            ("error", Severity::Error),
+            ("warning", Severity::Warning),
+            ("info", Severity::Info),
+            ("note", Severity::Info),
+        ]
+        .into_iter()
+        .find_map(|(word, severity)| {
+            let after = rest.get(word.len()..)?;
+            (rest[..word.len()].eq_ignore_ascii_case(word)
+                && !after.starts_with(|c: char| c.is_ascii_alphabetic()))
+            .then_some((severity, after.trim_start()))
+        })?;
+        let message = match after.split_once(':') {
+            Some((code, message))
+                if !code.is_empty()
+                    && code.chars().all(|c| c.is_ascii_alphanumeric())
+                    && code.chars().any(|c| c.is_ascii_digit()) =>
+            {
+                message
+            }
+            _ => after.strip_prefix(':').unwrap_or(after),
+        };
+        Some((severity, message.trim()))
+    }
+
+    fn guess_severity(message: &str) -> Severity {
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("warning") {
+            Severity::Warning
+        } else {
+            Severity::Error
+        }
+    }
+}
+
+/// Why a shader failed to compile, with the compiler's log broken into entries.
+///
+/// Its `Display` shows each entry against the offending source line:
+/// ```rust
+/// use vita_gl_helpers::{diagnostic::ShaderDiagnostic, shader::ShaderStage};
+/// let source = "void main(float4 out gl_Position : POSITION) {\n\n    gl_Position = foo;\n}";
+/// let log = "E] undefined variable 'foo' on line 3";
+/// let diagnostic = ShaderDiagnostic::parse(ShaderStage::Vertex, Some("vert.cg"), source, log);
+/// assert_eq!(
+///     diagnostic.to_string(),
+///     "\
+/// error: vertex shader `vert.cg` did not compile
+/// error: undefined variable 'foo'
+///  --> vert.cg:3
+///   |
+/// 3 |     gl_Position = foo;
+///   |     ^^^^^^^^^^^^^^^^^^
+/// "
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderStage,
+    /// Where the source came from, e.g. a file name.
+    pub source_name: Option<String>,
+    pub source: String,
+    /// The info log exactly as the compiler gave it.
+    pub log: String,
+    pub entries: Vec<DiagnosticEntry>,
+}
+
+impl ShaderDiagnostic {
+    pub fn parse(stage: ShaderStage, source_name: Option<&str>, source: &str, log: &str) -> Self {
+        let log = log.trim_end_matches('\0');
+        ShaderDiagnostic {
+            stage,
+            source_name: source_name.map(str::to_string),
+            source: source.to_string(),
+            log: log.to_string(),
+            entries: log.lines().filter_map(DiagnosticEntry::parse).collect(),
+        }
+    }
+    /// Points each entry back at the file and line it came from before preprocessing.
+    pub fn remap(&mut self, line_map: &LineMap) {
+        for entry in &mut self.entries {
+            entry.origin = entry
+                .line
+                .and_then(|line| line_map.lookup(line))
+                .map(|(file, line)| (file.to_string(), line));
+        }
+    }
+    pub fn errors(&self) -> impl Iterator<Item = &DiagnosticEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.severity == Severity::Error)
+    }
+    fn render_entry(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        entry: &DiagnosticEntry,
+    ) -> std::fmt::Result {
+        writeln!(f, "{}: {}", entry.severity, entry.message)?;
+        let Some(line) = entry.line else {
+            return Ok(());
+        };
+        let (name, shown_line) = match &entry.origin {
+            Some((file, origin_line)) => (file.as_str(), *origin_line),
+            None => (self.source_name.as_deref().unwrap_or("<source>"), line),
+        };
+        let gutter = shown_line.to_string().len();
+        match entry.column {
+            Some(column) => writeln!(f, "{:gutter$}--> {name}:{shown_line}:{column}", "")?,
+            None => writeln!(f, "{:gutter$}--> {name}:{shown_line}", "")?,
+        }
+        let Some(text) = self.source.lines().nth(line as usize - 1) else {
+            return Ok(());
+        };
+        let text = text.trim_end();
+        writeln!(f, "{:gutter$} |", "")?;
+        writeln!(f, "{shown_line} | {text}")?;
+        let (start, len) = match entry.column {
+            Some(column) => (column as usize - 1, 1),
+            None => {
+                let start = text.len() - text.trim_start().len();
+                (start, (text.len() - start).max(1))
+            }
+        };
+        let indent: String = text
+            .chars()
+            .take(start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{:gutter$} | {indent}{}", "", "^".repeat(len))
+    }
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source_name {
+            Some(name) => writeln!(f, "error: {} `{name}` did not compile", self.stage)?,
+            None => writeln!(f, "error: {} did not compile", self.stage)?,
+        }
+        if self.entries.is_empty() {
+            return write!(f, "{}", self.log);
+        }
+        for entry in &self.entries {
+            self.render_entry(f, entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess::{EmbeddedResolver, ShaderSourceBuilder};
+
+    /// The line, column, severity and message of `log_line`.
+    fn parts(log_line: &str) -> (Option<u32>, Option<u32>, Severity, String) {
+        let entry = DiagnosticEntry::parse(log_line).unwrap();
+        (entry.line, entry.column, entry.severity, entry.message)
+    }
+
+    #[test]
+    fn vitagl_form() {
+        assert_eq!(
+            parts("E] undefined variable 'foo' on line 12"),
+            (
+                Some(12),
+                None,
+                Severity::Error,
+                "undefined variable 'foo'".into()
+            )
+        );
+        assert_eq!(
+            parts("W] implicit truncation"),
+            (None, None, Severity::Warning, "implicit truncation".into())
+        );
+        assert_eq!(
+            parts("I] on line 0 means nowhere on line 0"),
+            (None, None, Severity::Info, "on line 0 means nowhere".into())
+        );
+    }
+
+    #[test]
+    fn paren_form() {
+        assert_eq!(
+            parts("vert.cg(3,5): error C1008: undefined variable \"foo\""),
+            (
+                Some(3),
+                Some(5),
+                Severity::Error,
+                "undefined variable \"foo\"".into()
+            )
+        );
+        assert_eq!(
+            parts("(7): warning: unused parameter"),
+            (Some(7), None, Severity::Warning, "unused parameter".into())
+        );
+    }
+
+    #[test]
+    fn colon_form() {
+        assert_eq!(
+            parts("vert.cg:3:5: error: syntax error"),
+            (Some(3), Some(5), Severity::Error, "syntax error".into())
+        );
+        assert_eq!(
+            parts("frag.cg:9: note: declared here"),
+            (Some(9), None, Severity::Info, "declared here".into())
+        );
+    }
+
+    #[test]
+    fn no_location_guesses_severity() {
+        assert_eq!(
+            parts("Warning - falling back to software"),
+            (
+                None,
+                None,
+                Severity::Warning,
+                "Warning - falling back to software".into()
+            )
+        );
+        assert_eq!(
+            parts("compilation failed"),
+            (None, None, Severity::Error, "compilation failed".into())
+        );
+        assert_eq!(DiagnosticEntry::parse("   "), None);
+    }
+
+    /// Line 10 is `\treturn tint;`, for a two-digit gutter.
+    fn source() -> String {
+        format!("{}\treturn tint;\n}}\n", "\n".repeat(9))
+    }
+
+    #[test]
+    fn display_with_column() {
+        let diagnostic = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            None,
+            &source(),
+            "(10,9): error: undefined variable\0",
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "\
+error: fragment shader did not compile
+error: undefined variable
+  --> <source>:10:9
+   |
+10 | \treturn tint;
+   | \t       ^
+"
+        );
+    }
+
+    #[test]
+    fn display_without_column() {
+        let diagnostic = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            Some("frag.cg"),
+            &source(),
+            "E] undefined variable on line 10\nW] no location",
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "\
+error: fragment shader `frag.cg` did not compile
+error: undefined variable
+  --> frag.cg:10
+   |
+10 | \treturn tint;
+   | \t^^^^^^^^^^^^
+warning: no location
+"
+        );
+    }
+
+    #[test]
+    fn display_remapped() {
+        let resolver = EmbeddedResolver::new([
+            (
+                "frag.cg",
+                "#include \"tint.cg\"\nfloat4 main() : COLOR { return tint; }",
+            ),
+            ("tint.cg", "// Tint\nstatic float4 tint = TINT;"),
+        ]);
+        let source = ShaderSourceBuilder::new(resolver)
+            .define("TINT", "1.0")
+            .build("frag.cg")
+            .unwrap();
+        let mut diagnostic = ShaderDiagnostic::parse(
+            ShaderStage::Fragment,
+            Some("frag.cg"),
+            &source.text,
+            "E] cannot convert on line 3",
+        );
+        diagnostic.remap(&source.line_map);
+        assert_eq!(
+            diagnostic.to_string(),
+            "\
+error: fragment shader `frag.cg` did not compile
+error: cannot convert
+ --> tint.cg:2
+  |
+2 | static float4 tint = TINT;
+  | ^^^^^^^^^^^^^^^^^^^^^^^^^^
+"
+        );
+    }
+
+    #[test]
+    fn display_without_entries_shows_log() {
+        let diagnostic = ShaderDiagnostic::parse(ShaderStage::Vertex, None, "", "\n\n");
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: vertex shader did not compile\n\n\n"
+        );
+    }
+}
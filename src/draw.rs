@@ -2,8 +2,9 @@ use std::ffi::c_void;
 
 use gl::types::{GLenum, GLint, GLsizei};
 
-use crate::buffer::Buffer;
+use crate::{GlContext, buffer::Buffer, errors::check_wrapper};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum Mode {
     Points = gl::POINTS,
@@ -12,8 +13,10 @@ pub enum Mode {
     Quads = gl::QUADS,
 }
 
-pub fn draw_arrays(mode: Mode, first: GLint, count: GLsizei) {
+#[cfg_attr(gl_check, track_caller)]
+pub fn draw_arrays(_ctx: &GlContext, mode: Mode, first: GLint, count: GLsizei) {
     unsafe { gl::DrawArrays(mode as _, first, count) }
+    check_wrapper!("draw_arrays", mode, first, count);
 }
 
 pub struct ElementParams {
@@ -23,15 +26,19 @@ pub struct ElementParams {
 }
 
 pub trait Elements {
-    fn use_me(&self) -> ElementParams;
-    fn draw(&self, mode: Mode) {
-        let params = self.use_me();
+    #[cfg_attr(gl_check, track_caller)]
+    fn use_me(&self, ctx: &GlContext) -> ElementParams;
+    #[cfg_attr(gl_check, track_caller)]
+    fn draw(&self, ctx: &GlContext, mode: Mode) {
+        let params = self.use_me(ctx);
         unsafe {
             gl::DrawElements(mode as _, params.count, params.type_, params.indices);
         }
+        check_wrapper!("Elements::draw", mode, params.count, params.type_);
     }
-    fn draw_instanced(&self, mode: Mode, primcount: GLsizei) {
-        let params = self.use_me();
+    #[cfg_attr(gl_check, track_caller)]
+    fn draw_instanced(&self, ctx: &GlContext, mode: Mode, primcount: GLsizei) {
+        let params = self.use_me(ctx);
         unsafe {
             gl::DrawElementsInstanced(
                 mode as _,
@@ -41,6 +48,13 @@ pub trait Elements {
                 primcount,
             );
         }
+        check_wrapper!(
+            "Elements::draw_instanced",
+            mode,
+            params.count,
+            params.type_,
+            primcount
+        );
     }
 }
 
@@ -49,8 +63,9 @@ pub struct ElementsU16<'a> {
 }
 
 impl<'a> Elements for ElementsU16<'a> {
-    fn use_me(&self) -> ElementParams {
-        Buffer::default().bind(gl::ELEMENT_ARRAY_BUFFER);
+    #[cfg_attr(gl_check, track_caller)]
+    fn use_me(&self, ctx: &GlContext) -> ElementParams {
+        Buffer::default().bind(ctx, gl::ELEMENT_ARRAY_BUFFER);
         ElementParams {
             count: self.indices.len() as _,
             type_: gl::UNSIGNED_SHORT,
@@ -64,8 +79,9 @@ pub struct ElementsU32<'a> {
 }
 
 impl<'a> Elements for ElementsU32<'a> {
-    fn use_me(&self) -> ElementParams {
-        Buffer::default().bind(gl::ELEMENT_ARRAY_BUFFER);
+    #[cfg_attr(gl_check, track_caller)]
+    fn use_me(&self, ctx: &GlContext) -> ElementParams {
+        Buffer::default().bind(ctx, gl::ELEMENT_ARRAY_BUFFER);
         ElementParams {
             count: self.indices.len() as _,
             type_: gl::UNSIGNED_INT,
@@ -80,8 +96,9 @@ pub struct ElementsBufU16 {
 }
 
 impl Elements for ElementsBufU16 {
-    fn use_me(&self) -> ElementParams {
-        self.indices.bind(gl::ELEMENT_ARRAY_BUFFER);
+    #[cfg_attr(gl_check, track_caller)]
+    fn use_me(&self, ctx: &GlContext) -> ElementParams {
+        self.indices.bind(ctx, gl::ELEMENT_ARRAY_BUFFER);
         ElementParams {
             count: self.len as _,
             type_: gl::UNSIGNED_SHORT,
@@ -96,8 +113,9 @@ pub struct ElementsBufU32 {
 }
 
 impl Elements for ElementsBufU32 {
-    fn use_me(&self) -> ElementParams {
-        self.indices.bind(gl::ELEMENT_ARRAY_BUFFER);
+    #[cfg_attr(gl_check, track_caller)]
+    fn use_me(&self, ctx: &GlContext) -> ElementParams {
+        self.indices.bind(ctx, gl::ELEMENT_ARRAY_BUFFER);
         ElementParams {
             count: self.len as _,
             type_: gl::UNSIGNED_INT,
@@ -109,3 +127,74 @@ impl Elements for ElementsBufU32 {
 // pub struct Elements<T: AsRef<[U]>,U> {
 //     indices: T
 // }
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, GlCall};
+
+    #[test]
+    fn draw_arrays_calls() {
+        let ctx = mock::install();
+        mock::reset();
+        draw_arrays(&ctx, Mode::Quads, 4, 8);
+        assert_eq!(
+            mock::take_calls(),
+            [GlCall::DrawArrays {
+                mode: gl::QUADS,
+                first: 4,
+                count: 8,
+            }]
+        );
+    }
+
+    #[test]
+    fn client_elements_unbind_element_buffer() {
+        let ctx = mock::install();
+        mock::reset();
+        let indices = [0u32, 1, 2, 2, 3, 0];
+        ElementsU32 { indices: &indices }.draw(&ctx, Mode::Triangles);
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::BindBuffer {
+                    target: gl::ELEMENT_ARRAY_BUFFER,
+                    buffer: 0,
+                },
+                GlCall::DrawElements {
+                    mode: gl::TRIANGLES,
+                    count: 6,
+                    type_: gl::UNSIGNED_INT,
+                    indices: indices.as_ptr() as usize,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn buffer_elements_draw_instanced() {
+        let ctx = mock::install();
+        mock::reset();
+        let elements = ElementsBufU16 {
+            indices: Buffer::from(3),
+            len: 6,
+        };
+        elements.draw_instanced(&ctx, Mode::Triangles, 20);
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::BindBuffer {
+                    target: gl::ELEMENT_ARRAY_BUFFER,
+                    buffer: 3,
+                },
+                GlCall::DrawElementsInstanced {
+                    mode: gl::TRIANGLES,
+                    count: 6,
+                    type_: gl::UNSIGNED_SHORT,
+                    indices: 0,
+                    primcount: 20,
+                },
+            ]
+        );
+    }
+}
@@ -1,17 +1,54 @@
-use derive_more::TryFrom;
+use std::{
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
 
-#[derive(TryFrom, Debug, PartialEq, Eq)]
-#[try_from(repr)]
-#[repr(u32)]
+use crate::{GlContext, context::NotSend, logging};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GlError {
-    NoError = gl::NO_ERROR,
-    InvalidEnum = gl::INVALID_ENUM,
-    InvalidValue = gl::INVALID_VALUE,
-    InvalidOperation = gl::INVALID_OPERATION,
-    InvalidFramebufferOperation = gl::INVALID_FRAMEBUFFER_OPERATION,
-    OutOfMemory = gl::OUT_OF_MEMORY,
-    StackUnderflow = gl::STACK_UNDERFLOW,
-    StackOverflow = gl::STACK_OVERFLOW,
+    NoError,
+    InvalidEnum,
+    InvalidValue,
+    InvalidOperation,
+    InvalidFramebufferOperation,
+    OutOfMemory,
+    StackUnderflow,
+    StackOverflow,
+    /// A code GL doesn't define, e.g. a vendor extension's.
+    Unknown(u32),
+}
+
+impl From<u32> for GlError {
+    fn from(code: u32) -> Self {
+        match code {
+            gl::NO_ERROR => GlError::NoError,
+            gl::INVALID_ENUM => GlError::InvalidEnum,
+            gl::INVALID_VALUE => GlError::InvalidValue,
+            gl::INVALID_OPERATION => GlError::InvalidOperation,
+            gl::INVALID_FRAMEBUFFER_OPERATION => GlError::InvalidFramebufferOperation,
+            gl::OUT_OF_MEMORY => GlError::OutOfMemory,
+            gl::STACK_UNDERFLOW => GlError::StackUnderflow,
+            gl::STACK_OVERFLOW => GlError::StackOverflow,
+            code => GlError::Unknown(code),
+        }
+    }
+}
+
+impl From<GlError> for u32 {
+    fn from(error: GlError) -> Self {
+        match error {
+            GlError::NoError => gl::NO_ERROR,
+            GlError::InvalidEnum => gl::INVALID_ENUM,
+            GlError::InvalidValue => gl::INVALID_VALUE,
+            GlError::InvalidOperation => gl::INVALID_OPERATION,
+            GlError::InvalidFramebufferOperation => gl::INVALID_FRAMEBUFFER_OPERATION,
+            GlError::OutOfMemory => gl::OUT_OF_MEMORY,
+            GlError::StackUnderflow => gl::STACK_UNDERFLOW,
+            GlError::StackOverflow => gl::STACK_OVERFLOW,
+            GlError::Unknown(code) => code,
+        }
+    }
 }
 
 impl std::fmt::Display for GlError {
@@ -28,27 +65,50 @@ impl std::fmt::Display for GlError {
                 GlError::OutOfMemory => "GL_OUT_OF_MEMORY",
                 GlError::StackUnderflow => "GL_STACK_UNDERFLOW",
                 GlError::StackOverflow => "GL_STACK_OVERFLOW",
+                GlError::Unknown(code) => return write!(f, "unknown GL error {code:#06x}"),
             }
         )
     }
 }
 
-pub fn get_error() -> GlError {
-    unsafe {
-        gl::GetError()
-            .try_into()
-            .expect("Unexpected Error in glGetError")
-    }
+impl std::error::Error for GlError {}
+
+pub fn get_error(_ctx: &GlContext) -> GlError {
+    unsafe { gl::GetError() }.into()
+}
+
+/// The pending GL errors, oldest first.
+///
+/// Stops after [`Errors::LIMIT`] errors, since `glGetError` can keep failing forever,
+/// e.g. without a current context.
+pub struct Errors {
+    remaining: usize,
+    _not_send: NotSend,
 }
 
-pub struct Errors;
+impl Errors {
+    /// More than GL has error flags, so only a broken `glGetError` reaches it.
+    pub const LIMIT: usize = 32;
+
+    pub fn new(_ctx: &GlContext) -> Self {
+        Errors {
+            remaining: Self::LIMIT,
+            _not_send: PhantomData,
+        }
+    }
+}
 
 impl Iterator for Errors {
     type Item = GlError;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next_error = get_error();
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let next_error = unsafe { gl::GetError() }.into();
         if next_error == GlError::NoError {
+            self.remaining = 0;
             None
         } else {
             Some(next_error)
@@ -56,8 +116,265 @@ impl Iterator for Errors {
     }
 }
 
-pub fn eprintln_errors() {
-    for error in Errors {
-        eprintln!("GL ERROR: {error}");
+/// A GL error and the call it was noticed after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlErrorReport {
+    pub error: GlError,
+    /// The wrapper, e.g. `Buffer::bind`, or the expression passed to [`gl_check!`].
+    pub call: &'static str,
+    /// The wrapper's arguments, `Debug`-formatted. Empty for [`gl_check!`].
+    pub args: String,
+    /// Where the call was made from.
+    pub file: &'static str,
+    pub line: u32,
+}
+
+impl std::fmt::Display for GlErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} after {}", self.error, self.call)?;
+        if !self.args.is_empty() {
+            write!(f, "({})", self.args)?;
+        }
+        write!(f, " at {}:{}", self.file, self.line)
+    }
+}
+
+/// Drains [`Errors`], reporting each against `call`. Used by [`gl_check!`] and the
+/// `gl-check` feature.
+#[doc(hidden)]
+pub fn check_errors(
+    ctx: &GlContext,
+    call: &'static str,
+    args: impl FnOnce() -> String,
+    file: &'static str,
+    line: u32,
+) {
+    let mut errors = Errors::new(ctx).peekable();
+    if errors.peek().is_none() {
+        return;
+    }
+    let args = args();
+    for error in errors {
+        report(GlErrorReport {
+            error,
+            call,
+            args: args.clone(),
+            file,
+            line,
+        });
+    }
+}
+
+type ErrorCallback = Arc<dyn Fn(&GlErrorReport) + Send + Sync>;
+
+static ERROR_CALLBACK: RwLock<Option<ErrorCallback>> = RwLock::new(None);
+
+/// Sends every GL error the crate reports to `callback` instead of the log, e.g. to show
+/// them on screen or to panic in tests. Replaces any earlier callback.
+pub fn set_error_callback(callback: impl Fn(&GlErrorReport) + Send + Sync + 'static) {
+    *ERROR_CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(callback));
+}
+
+/// Goes back to logging GL errors.
+pub fn clear_error_callback() {
+    *ERROR_CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Passes `report` to the [error callback](set_error_callback), or logs it at error level
+/// if there is none.
+///
+/// The callback runs without the lock held, so it may replace or clear itself.
+pub fn report(report: GlErrorReport) {
+    let callback = ERROR_CALLBACK
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    match callback {
+        Some(callback) => callback(&report),
+        None => logging::error!("GL ERROR: {report}"),
+    }
+}
+
+/// [Reports](report) every pending GL error against the caller, e.g. once a frame.
+#[track_caller]
+pub fn report_errors(ctx: &GlContext) {
+    let caller = std::panic::Location::caller();
+    check_errors(
+        ctx,
+        "report_errors",
+        String::new,
+        caller.file(),
+        caller.line(),
+    );
+}
+
+/// Evaluates a GL call, then reports any pending GL error with the call and its location.
+/// ```rust,no_run
+/// use vita_gl_helpers::gl_check;
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
+/// let texture = 1;
+/// gl_check!(&ctx, unsafe { gl::BindTexture(gl::TEXTURE_2D, texture) });
+/// ```
+/// Errors from earlier calls are reported too, so checking after each suspect call
+/// narrows down the one that failed.
+#[macro_export]
+macro_rules! gl_check {
+    ($ctx:expr, $call:expr) => {{
+        let value = $call;
+        $crate::errors::check_errors($ctx, stringify!($call), String::new, file!(), line!());
+        value
+    }};
+}
+
+/// Reports pending GL errors against the calling wrapper and its caller, when built with
+/// the `gl-check` feature and debug assertions. Expands to nothing otherwise, so the
+/// arguments aren't even evaluated. Wrappers using it need
+/// `#[cfg_attr(gl_check, track_caller)]` so the caller's location is the one reported.
+macro_rules! check_wrapper {
+    ($call:expr $(, $arg:expr)* $(,)?) => {
+        #[cfg(gl_check)]
+        {
+            let caller = ::std::panic::Location::caller();
+            // Wrappers only get here after calling GL, which took a context to do.
+            #[allow(unused_unsafe)]
+            let ctx = unsafe { $crate::GlContext::assume_current() };
+            $crate::errors::check_errors(
+                &ctx,
+                $call,
+                || {
+                    let args: Vec<String> = vec![$(format!("{:?}", $arg)),*];
+                    args.join(", ")
+                },
+                caller.file(),
+                caller.line(),
+            );
+        }
+    };
+}
+pub(crate) use check_wrapper;
+
+/// The same as [`report_errors`]: despite the name, errors go to the
+/// [error callback](set_error_callback) or the log.
+#[track_caller]
+pub fn eprintln_errors(ctx: &GlContext) {
+    let caller = std::panic::Location::caller();
+    check_errors(
+        ctx,
+        "eprintln_errors",
+        String::new,
+        caller.file(),
+        caller.line(),
+    );
+}
+
+#[cfg(all(test, feature = "host"))]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::mock;
+
+    thread_local! {
+        static REPORTS: RefCell<Vec<GlErrorReport>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Installs the mock with every report collected per thread, so tests can run in
+    /// parallel. The callback is never cleared, since other threads may be using it.
+    pub(crate) fn install() -> GlContext {
+        set_error_callback(capture);
+        let ctx = mock::install();
+        mock::reset();
+        REPORTS.with_borrow_mut(Vec::clear);
+        ctx
+    }
+
+    fn capture(report: &GlErrorReport) {
+        REPORTS.with_borrow_mut(|r| r.push(report.clone()));
+    }
+
+    pub(crate) fn take_reports() -> Vec<GlErrorReport> {
+        REPORTS.with_borrow_mut(std::mem::take)
+    }
+
+    #[test]
+    fn gl_check_reports_every_pending_error() {
+        let ctx = install();
+        mock::with(|m| m.errors.extend([gl::INVALID_ENUM, gl::OUT_OF_MEMORY]));
+        let line = line!() + 1;
+        gl_check!(&ctx, unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) });
+        let reports = take_reports();
+        let errors: Vec<GlError> = reports.iter().map(|r| r.error).collect();
+        assert_eq!(errors, [GlError::InvalidEnum, GlError::OutOfMemory]);
+        assert!(reports[0].call.contains("gl::Clear(gl::COLOR_BUFFER_BIT)"));
+        assert_eq!((reports[0].file, reports[0].line), (file!(), line));
+        assert!(reports[0].args.is_empty());
+    }
+
+    #[test]
+    fn gl_check_without_errors_reports_nothing() {
+        let ctx = install();
+        gl_check!(&ctx, unsafe { gl::Clear(gl::COLOR_BUFFER_BIT) });
+        assert!(take_reports().is_empty());
+        assert_eq!(
+            mock::take_calls(),
+            [mock::GlCall::Clear(gl::COLOR_BUFFER_BIT)]
+        );
+    }
+
+    #[test]
+    fn errors_stop_at_limit() {
+        let ctx = install();
+        mock::with(|m| m.errors.extend([gl::INVALID_VALUE; Errors::LIMIT + 8]));
+        assert_eq!(Errors::new(&ctx).count(), Errors::LIMIT);
+    }
+
+    #[test]
+    fn report_errors_blames_caller() {
+        let ctx = install();
+        mock::with(|m| m.errors.push_back(0x1234));
+        let line = line!() + 1;
+        report_errors(&ctx);
+        let report = &take_reports()[0];
+        assert_eq!(report.error, GlError::Unknown(0x1234));
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "unknown GL error 0x1234 after report_errors at {}:{line}",
+                file!()
+            )
+        );
+    }
+
+    #[cfg(gl_check)]
+    #[test]
+    fn wrappers_report_their_caller() {
+        let ctx = install();
+        mock::with(|m| m.errors.push_back(gl::INVALID_OPERATION));
+        let line = line!() + 1;
+        crate::draw::draw_arrays(&ctx, crate::draw::Mode::Triangles, 0, 3);
+        let reports = take_reports();
+        assert_eq!(
+            reports,
+            [GlErrorReport {
+                error: GlError::InvalidOperation,
+                call: "draw_arrays",
+                args: "Triangles, 0, 3".into(),
+                file: file!(),
+                line,
+            }]
+        );
+    }
+
+    #[test]
+    fn callback_can_replace_itself() {
+        let ctx = install();
+        // Puts `capture` back before passing the report on, so other tests keep theirs.
+        set_error_callback(|report| {
+            set_error_callback(capture);
+            capture(report);
+        });
+        mock::with(|m| m.errors.push_back(gl::INVALID_ENUM));
+        report_errors(&ctx);
+        assert_eq!(take_reports()[0].error, GlError::InvalidEnum);
     }
 }
@@ -0,0 +1,29 @@
+//! Stand-ins for the vitaGL entry points, used by the `host` feature.
+//!
+//! These do nothing, so GL itself has to come from the loader passed to
+//! [`initialise_extended_with`](crate::initialise_extended_with).
+#![allow(non_snake_case, clippy::missing_safety_doc)]
+
+pub unsafe fn vglSwapBuffers(_has_commondialog: u8) {}
+
+pub unsafe fn vglSetupRuntimeShaderCompiler(
+    _opt_level: i32,
+    _use_fastmath: i32,
+    _use_fastprecision: i32,
+    _use_fastint: i32,
+) {
+}
+
+pub unsafe fn vglInitExtended(
+    _legacy_pool_size: i32,
+    _width: i32,
+    _height: i32,
+    _ram_threshold: i32,
+    _msaa: u32,
+) -> u8 {
+    gl::TRUE
+}
+
+pub unsafe fn vglGetProcAddress(_name: *const u8) -> *const u8 {
+    std::ptr::null()
+}
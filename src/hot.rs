@@ -0,0 +1,267 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use derive_more::From;
+
+use crate::{
+    GlContext,
+    attribute::{AttributeTable, MissingAttributes},
+    logging,
+    program::{OwnedProgram, ProgramError},
+    shader::{ShaderError, ShaderStage, load_shader_named},
+    uniforms::{MissingUniforms, UniformTable},
+    variant::Variant,
+};
+
+#[derive(Debug, From)]
+pub enum HotReloadError {
+    Io(io::Error),
+    Shader(ShaderError),
+    Program(ProgramError),
+    MissingUniforms(MissingUniforms),
+    MissingAttributes(MissingAttributes),
+}
+
+impl std::fmt::Display for HotReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HotReloadError::Io(e) => write!(f, "{e}"),
+            HotReloadError::Shader(e) => write!(f, "{e}"),
+            HotReloadError::Program(e) => write!(f, "{e}"),
+            HotReloadError::MissingUniforms(e) => write!(f, "{e}"),
+            HotReloadError::MissingAttributes(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for HotReloadError {}
+
+/// A program loaded from two source files, rebuilt whenever [`poll`](HotProgram::poll)
+/// finds that either has changed. Meant for tuning shaders on a devkit without redeploying.
+///
+/// If a rebuild fails the last good program and tables stay in use.
+/// ```rust,no_run
+/// use vita_gl_helpers::{
+///     attribute::{Attribute, AttributeTable},
+///     hot::HotProgram,
+///     uniforms::{Uniform1fv, UniformTable},
+/// };
+/// #[derive(AttributeTable)]
+/// struct Attributes {
+///     #[gl(name = "aPos")]
+///     pos: Attribute,
+/// }
+/// #[derive(UniformTable)]
+/// struct Uniforms {
+///     time: Uniform1fv,
+/// }
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
+/// let mut program = HotProgram::<Uniforms, Attributes>::load(
+///     &ctx,
+///     "ux0:data/shaders/vert.cg",
+///     "ux0:data/shaders/frag.cg",
+/// )
+/// .unwrap();
+/// loop {
+///     program.poll(&ctx);
+///     program.use_me(&ctx);
+///     program.uniforms().time.set(&ctx, 0.5);
+/// }
+/// ```
+pub struct HotProgram<U, A> {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    modified: [Option<SystemTime>; 2],
+    current: Variant<U, A>,
+    last_error: Option<HotReloadError>,
+}
+
+impl<U: UniformTable, A: AttributeTable> HotProgram<U, A> {
+    /// Builds the program for the first time. Unlike later reloads, this fails if it can't.
+    pub fn load(
+        ctx: &GlContext,
+        vertex: impl Into<PathBuf>,
+        fragment: impl Into<PathBuf>,
+    ) -> Result<Self, HotReloadError> {
+        let (vertex, fragment) = (vertex.into(), fragment.into());
+        let modified = [modified(&vertex), modified(&fragment)];
+        let current = build(ctx, &vertex, &fragment)?;
+        Ok(HotProgram {
+            vertex,
+            fragment,
+            modified,
+            current,
+            last_error: None,
+        })
+    }
+    /// Rebuilds the program if either source file has changed since the last poll.
+    ///
+    /// Returns whether a new program is now in use. On failure the error is logged as a warning,
+    /// kept in [`last_error`](HotProgram::last_error), and the old program is kept.
+    /// Each poll checks both files' modification times, so every few frames is plenty.
+    pub fn poll(&mut self, ctx: &GlContext) -> bool {
+        let modified = [modified(&self.vertex), modified(&self.fragment)];
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        self.reload(ctx)
+    }
+    /// Rebuilds the program now, whether or not the files have changed.
+    pub fn reload(&mut self, ctx: &GlContext) -> bool {
+        match build(ctx, &self.vertex, &self.fragment) {
+            Ok(current) => {
+                self.current = current;
+                self.last_error = None;
+                true
+            }
+            Err(e) => {
+                logging::warning!("Shader reload failed, keeping the old program:\n{e}");
+                self.last_error = Some(e);
+                false
+            }
+        }
+    }
+    pub fn use_me(&self, ctx: &GlContext) {
+        self.current.use_me(ctx)
+    }
+    /// The program in use. Its handle changes on every successful reload.
+    pub fn program(&self) -> &OwnedProgram {
+        &self.current.program
+    }
+    pub fn uniforms(&self) -> &U {
+        &self.current.uniforms
+    }
+    pub fn attributes(&self) -> &A {
+        &self.current.attributes
+    }
+    /// Why the latest reload failed, if it did.
+    pub fn last_error(&self) -> Option<&HotReloadError> {
+        self.last_error.as_ref()
+    }
+}
+
+/// `None` if the file can't be read right now, e.g. halfway through being replaced.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn build<U: UniformTable, A: AttributeTable>(
+    ctx: &GlContext,
+    vertex: &Path,
+    fragment: &Path,
+) -> Result<Variant<U, A>, HotReloadError> {
+    Variant::build(ctx, |stage| {
+        let path = match stage {
+            ShaderStage::Vertex => vertex,
+            ShaderStage::Fragment => fragment,
+        };
+        let source = std::fs::read_to_string(path)?;
+        Ok(load_shader_named(
+            ctx,
+            &source,
+            stage,
+            &path.to_string_lossy(),
+        )?)
+    })
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+    use crate::{
+        attribute_table,
+        mock::{self, GlCall},
+        preprocess::tests::TempDir,
+        uniform_table,
+    };
+
+    uniform_table!(Uniforms, time: Uniform1fv => "time");
+    attribute_table!(Attributes, pos => "aPos");
+
+    /// Rewrites `path` and moves its modification time `secs` into the future, as the
+    /// rewrite alone may not change it at the file system's granularity.
+    fn rewrite(path: &Path, text: &str, secs: u64) {
+        std::fs::write(path, text).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn load(dir: &TempDir) -> (GlContext, HotProgram<Uniforms, Attributes>) {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| {
+            m.add_uniform("time", gl::FLOAT, 1, 2);
+            m.add_attribute("aPos", gl::FLOAT_VEC2, 0);
+        });
+        let program = HotProgram::load(&ctx, dir.0.join("vert.cg"), dir.0.join("frag.cg"))
+            .ok()
+            .unwrap();
+        mock::take_calls();
+        (ctx, program)
+    }
+
+    #[test]
+    fn poll_reloads_changed_files() {
+        let dir = TempDir::new(&[("vert.cg", "vertex v1"), ("frag.cg", "fragment v1")]);
+        let (ctx, mut program) = load(&dir);
+        let first = u32::from(**program.program());
+        assert!(!program.poll(&ctx));
+        assert!(mock::take_calls().is_empty());
+
+        mock::with(|m| {
+            m.uniforms.clear();
+            m.add_uniform("time", gl::FLOAT, 1, 5);
+            m.attributes.clear();
+            m.add_attribute("aPos", gl::FLOAT_VEC2, 3);
+        });
+        rewrite(&dir.0.join("frag.cg"), "fragment v2", 10);
+        assert!(program.poll(&ctx));
+        assert!(!program.poll(&ctx));
+        assert_ne!(u32::from(**program.program()), first);
+        assert_eq!(program.uniforms().time.0, 5);
+        assert_eq!(program.attributes().pos.0, 3);
+        assert!(program.last_error().is_none());
+        let calls = mock::take_calls();
+        assert!(calls.iter().any(|call| matches!(
+            call,
+            GlCall::ShaderSource { source, .. } if source.contains("fragment v2")
+        )));
+        assert!(calls.contains(&GlCall::DeleteProgram(first)));
+    }
+
+    #[test]
+    fn failed_reload_keeps_program() {
+        let dir = TempDir::new(&[("vert.cg", "vertex v1"), ("frag.cg", "fragment v1")]);
+        let (ctx, mut program) = load(&dir);
+        let first = u32::from(**program.program());
+
+        mock::with(|m| {
+            m.compile_status = false;
+            m.info_log = "E] syntax error on line 1".into();
+        });
+        rewrite(&dir.0.join("vert.cg"), "vertex v2 {", 10);
+        assert!(!program.poll(&ctx));
+        assert_eq!(u32::from(**program.program()), first);
+        assert_eq!(program.uniforms().time.0, 2);
+        assert!(matches!(
+            program.last_error(),
+            Some(HotReloadError::Shader(ShaderError::Compile(_)))
+        ));
+        assert!(!mock::take_calls().contains(&GlCall::DeleteProgram(first)));
+
+        mock::with(|m| m.compile_status = true);
+        rewrite(&dir.0.join("vert.cg"), "vertex v3", 20);
+        assert!(program.poll(&ctx));
+        assert!(program.last_error().is_none());
+    }
+}
@@ -1,13 +1,33 @@
-use std::ffi::CString;
+use std::ffi::{CString, c_void};
+
+// Lets the derives' `::vita_gl_helpers` paths resolve in the crate's own tests.
+#[cfg(test)]
+extern crate self as vita_gl_helpers;
+
+pub use bytemuck;
 
 pub mod attribute;
 pub mod buffer;
+pub mod cache;
+pub mod cg;
+mod context;
+pub mod diagnostic;
 pub mod draw;
 pub mod errors;
+#[cfg(feature = "host")]
+mod host;
+pub mod hot;
+mod logging;
+#[cfg(feature = "host")]
+pub mod mock;
+pub mod preprocess;
 pub mod program;
+pub mod reflect;
 pub mod shader;
 pub mod texture;
 pub mod uniforms;
+pub mod variant;
+pub mod vertex;
 
 // #[link(name = "vitaGL", kind = "static")]
 // #[link(name = "vitashark", kind = "static")]
@@ -21,6 +41,7 @@ pub mod uniforms;
 // #[link(name = "SceDisplay_stub", kind = "static")]
 // #[link(name = "SceAppMgr_stub", kind = "static")]
 // #[link(name = "SceCommonDialog_stub", kind = "static")]
+#[cfg(not(feature = "host"))]
 unsafe extern "C" {
     pub fn vglSwapBuffers(has_commondialog: u8);
     pub fn vglSetupRuntimeShaderCompiler(
@@ -39,12 +60,17 @@ unsafe extern "C" {
     pub fn vglGetProcAddress(name: *const u8) -> *const u8;
 }
 
-pub fn swap_buffers() {
+pub use context::GlContext;
+#[cfg(feature = "host")]
+pub use host::*;
+
+pub fn swap_buffers(_ctx: &GlContext) {
     unsafe {
         vglSwapBuffers(0);
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RuntimeShaderCompilerSettings {
     pub opt_level: i32,
     pub use_fastmath: i32,
@@ -63,10 +89,63 @@ impl Default for RuntimeShaderCompilerSettings {
     }
 }
 
+/// The size of the framebuffer vitaGL renders to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Resolution {
+    /// The Vita's native resolution.
+    #[default]
+    R960x544,
+    R720x408,
+    R640x368,
+    R480x272,
+    /// Only available on a PS TV.
+    R1280x720,
+}
+
+impl Resolution {
+    pub fn width(self) -> i32 {
+        self.size().0
+    }
+    pub fn height(self) -> i32 {
+        self.size().1
+    }
+    pub fn size(self) -> (i32, i32) {
+        match self {
+            Resolution::R960x544 => (960, 544),
+            Resolution::R720x408 => (720, 408),
+            Resolution::R640x368 => (640, 368),
+            Resolution::R480x272 => (480, 272),
+            Resolution::R1280x720 => (1280, 720),
+        }
+    }
+}
+
+/// Multisample anti-aliasing, as `SceGxmMultisampleMode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u32)]
+pub enum Msaa {
+    #[default]
+    None = 0,
+    X2 = 1,
+    X4 = 2,
+}
+
+/// Settings for `vglInitExtended`.
+/// ```rust,no_run
+/// use vita_gl_helpers::{Msaa, Resolution, VglInitSettings, initialise_extended};
+/// let settings = VglInitSettings::new()
+///     .resolution(Resolution::R720x408)
+///     .msaa(Msaa::X4);
+/// let ctx = initialise_extended(Default::default(), settings).expect("vitaGL failed to start");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VglInitSettings {
+    /// Bytes for immediate mode and other legacy vertex data.
     pub legacy_pool_size: i32,
+    /// Bytes of RAM vitaGL leaves free before falling back to other memory.
     pub ram_threshold: i32,
-    pub msaa: u32,
+    pub msaa: Msaa,
+    pub resolution: Resolution,
 }
 
 impl Default for VglInitSettings {
@@ -74,27 +153,112 @@ impl Default for VglInitSettings {
         VglInitSettings {
             legacy_pool_size: 0,
             ram_threshold: 65 * 1024 * 1024,
-            msaa: 0,
+            msaa: Msaa::None,
+            resolution: Resolution::R960x544,
         }
     }
 }
 
-pub fn initialise_extended(rscs: RuntimeShaderCompilerSettings, vis: VglInitSettings) {
-    unsafe {
+impl VglInitSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn legacy_pool_size(mut self, bytes: i32) -> Self {
+        self.legacy_pool_size = bytes;
+        self
+    }
+    pub fn ram_threshold(mut self, bytes: i32) -> Self {
+        self.ram_threshold = bytes;
+        self
+    }
+    pub fn msaa(mut self, msaa: Msaa) -> Self {
+        self.msaa = msaa;
+        self
+    }
+    pub fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+    /// Checks the settings before they reach vitaGL, which doesn't.
+    pub fn validate(&self) -> Result<(), InitError> {
+        if self.legacy_pool_size < 0 {
+            return Err(InitError::NegativeLegacyPoolSize(self.legacy_pool_size));
+        }
+        if self.ram_threshold < 0 {
+            return Err(InitError::NegativeRamThreshold(self.ram_threshold));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    NegativeLegacyPoolSize(i32),
+    NegativeRamThreshold(i32),
+    /// `vglInitExtended` failed, e.g. because the resolution isn't available.
+    VglInitFailed,
+}
+
+impl std::fmt::Display for InitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitError::NegativeLegacyPoolSize(size) => {
+                write!(f, "legacy pool size can't be negative, got {size}")
+            }
+            InitError::NegativeRamThreshold(threshold) => {
+                write!(f, "RAM threshold can't be negative, got {threshold}")
+            }
+            InitError::VglInitFailed => write!(f, "vglInitExtended failed"),
+        }
+    }
+}
+
+impl std::error::Error for InitError {}
+
+pub fn initialise_extended(
+    rscs: RuntimeShaderCompilerSettings,
+    vis: VglInitSettings,
+) -> Result<GlContext, InitError> {
+    initialise_extended_with(rscs, vis, |name| {
+        let name = CString::new(name).unwrap();
+        unsafe { vglGetProcAddress(name.as_ptr() as _) as _ }
+    })
+}
+
+/// Like [`initialise_extended`], but loads GL through `load_with` instead of `vglGetProcAddress`.
+///
+/// With the `host` feature this is how a stand-in GL gets plugged in.
+/// GL isn't loaded if the settings are invalid or vitaGL fails to start. The returned
+/// [`GlContext`] only works on the calling thread.
+pub fn initialise_extended_with(
+    rscs: RuntimeShaderCompilerSettings,
+    vis: VglInitSettings,
+    load_with: impl FnMut(&'static str) -> *const c_void,
+) -> Result<GlContext, InitError> {
+    vis.validate()?;
+    let (width, height) = vis.resolution.size();
+    let started = unsafe {
         vglSetupRuntimeShaderCompiler(
             rscs.opt_level,
             rscs.use_fastmath,
             rscs.use_fastprecision,
             rscs.use_fastint,
         );
-        vglInitExtended(vis.legacy_pool_size, 960, 544, vis.ram_threshold, vis.msaa);
+        vglInitExtended(
+            vis.legacy_pool_size,
+            width,
+            height,
+            vis.ram_threshold,
+            vis.msaa as u32,
+        )
+    };
+    if started == 0 {
+        return Err(InitError::VglInitFailed);
     }
-    gl::load_with(|name| {
-        let name = CString::new(name).unwrap();
-        unsafe { vglGetProcAddress(name.as_ptr() as _) as _ }
-    });
+    gl::load_with(load_with);
+    Ok(unsafe { GlContext::assume_current() })
 }
 
-pub fn initialise_default() {
-    initialise_extended(Default::default(), Default::default());
+pub fn initialise_default() -> Result<GlContext, InitError> {
+    initialise_extended(Default::default(), Default::default())
 }
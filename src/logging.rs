@@ -0,0 +1,41 @@
+//! The crate's own diagnostics. With the `log` feature they go through the `log` facade,
+//! under the `vita_gl_helpers` target. Without it, warnings and errors go to stderr and
+//! everything else is dropped.
+
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::trace!(target: "vita_gl_helpers", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::debug!(target: "vita_gl_helpers", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! warning {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::warn!(target: "vita_gl_helpers", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        eprintln!($($arg)+);
+    }};
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::error!(target: "vita_gl_helpers", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        eprintln!($($arg)+);
+    }};
+}
+
+pub(crate) use {debug, error, trace, warning};
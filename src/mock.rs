@@ -0,0 +1,935 @@
+//! A recording stand-in for GL, available with the `host` feature.
+//!
+//! [`install`] points every GL function the crate uses at a fake that records
+//! its arguments into a per-thread log, so wrappers can be checked without a GPU:
+//! ```rust
+//! use vita_gl_helpers::{
+//!     attribute::{Attribute, AttributeFormat, AttributeSize, AttributeType},
+//!     buffer::Buffer,
+//!     mock::{self, GlCall},
+//! };
+//! let ctx = mock::install();
+//! let format = AttributeFormat {
+//!     size: AttributeSize::TWO,
+//!     type_: AttributeType::Float,
+//!     normalized: false,
+//! };
+//! Buffer::from(3).bind_to(&ctx, Attribute(1), format, 8, 0);
+//! assert_eq!(
+//!     mock::take_calls(),
+//!     [
+//!         GlCall::BindBuffer { target: gl::ARRAY_BUFFER, buffer: 3 },
+//!         GlCall::VertexAttribPointer {
+//!             index: 1,
+//!             size: 2,
+//!             type_: gl::FLOAT,
+//!             normalized: false,
+//!             stride: 8,
+//!             pointer: 0,
+//!         },
+//!     ]
+//! );
+//! ```
+//! Client-side element draws unbind the element buffer first:
+//! ```rust
+//! use vita_gl_helpers::{draw::{Elements, ElementsU16, Mode}, mock::{self, GlCall}};
+//! let ctx = mock::install();
+//! ElementsU16 { indices: &[0, 1, 2] }.draw(&ctx, Mode::Triangles);
+//! let calls = mock::take_calls();
+//! assert_eq!(calls[0], GlCall::BindBuffer { target: gl::ELEMENT_ARRAY_BUFFER, buffer: 0 });
+//! assert!(matches!(calls[1], GlCall::DrawElements { count: 3, type_: gl::UNSIGNED_SHORT, .. }));
+//! ```
+//! Locations and reflection are answered from [`MockGl::attributes`] and [`MockGl::uniforms`]:
+//! ```rust
+//! use vita_gl_helpers::{mock, program::Program};
+//! let ctx = mock::install();
+//! mock::with(|m| m.add_uniform("rect_dim", gl::FLOAT_VEC2, 1, 0));
+//! assert_eq!(
+//!     Program::from(1).describe(&ctx).to_string(),
+//!     "Attributes:\nUniforms:\n  float2 rect_dim (location 0)\n"
+//! );
+//! ```
+//! `glGetError` is answered from [`MockGl::errors`] but is not recorded.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    ffi::{CStr, c_void},
+};
+
+use gl::types::{GLboolean, GLchar, GLenum, GLfloat, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint};
+
+use crate::GlContext;
+
+/// One recorded GL call. Pointers into GL buffers are kept as offsets, client data is copied.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlCall {
+    GenBuffers(Vec<GLuint>),
+    DeleteBuffers(Vec<GLuint>),
+    BindBuffer {
+        target: GLenum,
+        buffer: GLuint,
+    },
+    BufferData {
+        target: GLenum,
+        data: Option<Vec<u8>>,
+        size: GLsizeiptr,
+        usage: GLenum,
+    },
+    BufferSubData {
+        target: GLenum,
+        offset: GLintptr,
+        data: Vec<u8>,
+    },
+    VertexAttribDivisor {
+        index: GLuint,
+        divisor: GLuint,
+    },
+    EnableVertexAttribArray(GLuint),
+    DisableVertexAttribArray(GLuint),
+    VertexAttribPointer {
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: bool,
+        stride: GLsizei,
+        pointer: usize,
+    },
+    DrawArrays {
+        mode: GLenum,
+        first: GLint,
+        count: GLsizei,
+    },
+    DrawElements {
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: usize,
+    },
+    DrawElementsInstanced {
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: usize,
+        primcount: GLsizei,
+    },
+    UseProgram(GLuint),
+    CreateProgram(GLuint),
+    AttachShader {
+        program: GLuint,
+        shader: GLuint,
+    },
+    DetachShader {
+        program: GLuint,
+        shader: GLuint,
+    },
+    BindAttribLocation {
+        program: GLuint,
+        index: GLuint,
+        name: String,
+    },
+    LinkProgram(GLuint),
+    ValidateProgram(GLuint),
+    DeleteProgram(GLuint),
+    GetProgramBinary(GLuint),
+    ProgramBinary {
+        program: GLuint,
+        format: GLenum,
+        binary: Vec<u8>,
+    },
+    GetProgramiv {
+        program: GLuint,
+        pname: GLenum,
+    },
+    GetProgramInfoLog(GLuint),
+    GetActiveAttrib {
+        program: GLuint,
+        index: GLuint,
+    },
+    GetActiveUniform {
+        program: GLuint,
+        index: GLuint,
+    },
+    GetAttribLocation {
+        program: GLuint,
+        name: String,
+    },
+    GetUniformLocation {
+        program: GLuint,
+        name: String,
+    },
+    CreateShader {
+        type_: GLenum,
+        shader: GLuint,
+    },
+    ShaderSource {
+        shader: GLuint,
+        source: String,
+    },
+    CompileShader(GLuint),
+    ShaderBinary {
+        shaders: Vec<GLuint>,
+        format: GLenum,
+        binary: Vec<u8>,
+    },
+    DeleteShader(GLuint),
+    GetShaderiv {
+        shader: GLuint,
+        pname: GLenum,
+    },
+    GetShaderInfoLog(GLuint),
+    GenTextures(Vec<GLuint>),
+    DeleteTextures(Vec<GLuint>),
+    BindTexture {
+        target: GLenum,
+        texture: GLuint,
+    },
+    TexImage2D {
+        target: GLenum,
+        level: GLint,
+        internalformat: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: usize,
+    },
+    GenerateMipmap(GLenum),
+    TexParameteri {
+        target: GLenum,
+        pname: GLenum,
+        param: GLint,
+    },
+    /// `glUniform{components}fv`
+    Uniformfv {
+        components: usize,
+        location: GLint,
+        count: GLsizei,
+        value: Vec<f32>,
+    },
+    /// `glUniform{components}iv`
+    Uniformiv {
+        components: usize,
+        location: GLint,
+        count: GLsizei,
+        value: Vec<i32>,
+    },
+    /// `glUniformMatrix{dim}fv`
+    UniformMatrixfv {
+        dim: usize,
+        location: GLint,
+        count: GLsizei,
+        transpose: bool,
+        value: Vec<f32>,
+    },
+    ClearColor([f32; 4]),
+    Clear(GLenum),
+}
+
+/// The per-thread state behind the fake GL functions.
+pub struct MockGl {
+    pub calls: Vec<GlCall>,
+    /// Handed out by `glGen*`/`glCreate*`, then incremented.
+    pub next_name: GLuint,
+    /// The active attributes of every program. Unknown names have location -1.
+    pub attributes: Vec<MockVariable>,
+    /// The active uniforms of every program. Unknown names have location -1.
+    pub uniforms: Vec<MockVariable>,
+    pub compile_status: bool,
+    pub link_status: bool,
+    pub validate_status: bool,
+    /// Returned by both `glGetShaderInfoLog` and `glGetProgramInfoLog`.
+    pub info_log: String,
+    /// Popped by `glGetError`, which returns `GL_NO_ERROR` once empty.
+    pub errors: VecDeque<GLenum>,
+    /// The type each `glCreateShader` call was given.
+    pub shader_types: HashMap<GLuint, GLenum>,
+    /// Returned by `glGetProgramBinary` for every program, with format [`MOCK_BINARY_FORMAT`].
+    pub program_binary: Vec<u8>,
+    /// Queued onto [`errors`](MockGl::errors) by every `glShaderBinary`, to reject binaries.
+    pub shader_binary_errors: Vec<GLenum>,
+}
+
+pub const MOCK_BINARY_FORMAT: GLenum = 0x4d4f;
+
+impl Default for MockGl {
+    fn default() -> Self {
+        MockGl {
+            calls: Vec::new(),
+            next_name: 1,
+            attributes: Vec::new(),
+            uniforms: Vec::new(),
+            compile_status: true,
+            link_status: true,
+            validate_status: true,
+            info_log: String::new(),
+            errors: VecDeque::new(),
+            shader_types: HashMap::new(),
+            program_binary: Vec::new(),
+            shader_binary_errors: Vec::new(),
+        }
+    }
+}
+
+/// An active attribute or uniform, as reported by `glGetActive*` and `glGet*Location`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockVariable {
+    pub name: String,
+    pub type_: GLenum,
+    /// Array length, 1 for non-arrays.
+    pub size: GLint,
+    pub location: GLint,
+}
+
+impl MockVariable {
+    /// The location of `name`, which may index into this variable if it's an array.
+    fn location_of(&self, name: &str) -> Option<GLint> {
+        let base = self.name.strip_suffix("[0]").unwrap_or(&self.name);
+        if name == self.name || name == base {
+            return Some(self.location);
+        }
+        let index = name
+            .strip_prefix(base)?
+            .strip_prefix('[')?
+            .strip_suffix(']')?
+            .parse::<GLint>()
+            .ok()?;
+        (index < self.size).then_some(self.location + index)
+    }
+}
+
+impl MockGl {
+    pub fn add_attribute(&mut self, name: &str, type_: GLenum, location: GLint) {
+        self.attributes.push(MockVariable {
+            name: name.into(),
+            type_,
+            size: 1,
+            location,
+        });
+    }
+    pub fn add_uniform(&mut self, name: &str, type_: GLenum, size: GLint, location: GLint) {
+        self.uniforms.push(MockVariable {
+            name: name.into(),
+            type_,
+            size,
+            location,
+        });
+    }
+    fn gen_name(&mut self) -> GLuint {
+        let name = self.next_name;
+        self.next_name += 1;
+        name
+    }
+}
+
+thread_local! {
+    static MOCK: RefCell<MockGl> = RefCell::new(MockGl::default());
+}
+
+/// Loads the mock into the `gl` crate. Safe to call from every test.
+///
+/// The mock keeps its state per thread, so the returned context is good for any thread
+/// that installs it.
+pub fn install() -> GlContext {
+    gl::load_with(loader);
+    unsafe { GlContext::assume_current() }
+}
+
+/// The mock as a loader, for [`initialise_extended_with`](crate::initialise_extended_with).
+pub fn loader(name: &'static str) -> *const c_void {
+    match name {
+        "glGenBuffers" => gen_buffers as *const c_void,
+        "glDeleteBuffers" => delete_buffers as *const c_void,
+        "glBindBuffer" => bind_buffer as *const c_void,
+        "glBufferData" => buffer_data as *const c_void,
+        "glBufferSubData" => buffer_sub_data as *const c_void,
+        "glVertexAttribDivisor" => vertex_attrib_divisor as *const c_void,
+        "glEnableVertexAttribArray" => enable_vertex_attrib_array as *const c_void,
+        "glDisableVertexAttribArray" => disable_vertex_attrib_array as *const c_void,
+        "glVertexAttribPointer" => vertex_attrib_pointer as *const c_void,
+        "glDrawArrays" => draw_arrays as *const c_void,
+        "glDrawElements" => draw_elements as *const c_void,
+        "glDrawElementsInstanced" => draw_elements_instanced as *const c_void,
+        "glGetError" => get_error as *const c_void,
+        "glUseProgram" => use_program as *const c_void,
+        "glCreateProgram" => create_program as *const c_void,
+        "glAttachShader" => attach_shader as *const c_void,
+        "glDetachShader" => detach_shader as *const c_void,
+        "glBindAttribLocation" => bind_attrib_location as *const c_void,
+        "glLinkProgram" => link_program as *const c_void,
+        "glValidateProgram" => validate_program as *const c_void,
+        "glDeleteProgram" => delete_program as *const c_void,
+        "glGetProgramBinary" => get_program_binary as *const c_void,
+        "glProgramBinary" => program_binary as *const c_void,
+        "glGetProgramiv" => get_programiv as *const c_void,
+        "glGetProgramInfoLog" => get_program_info_log as *const c_void,
+        "glGetActiveAttrib" => get_active_attrib as *const c_void,
+        "glGetActiveUniform" => get_active_uniform as *const c_void,
+        "glGetAttribLocation" => get_attrib_location as *const c_void,
+        "glGetUniformLocation" => get_uniform_location as *const c_void,
+        "glCreateShader" => create_shader as *const c_void,
+        "glShaderSource" => shader_source as *const c_void,
+        "glCompileShader" => compile_shader as *const c_void,
+        "glShaderBinary" => shader_binary as *const c_void,
+        "glDeleteShader" => delete_shader as *const c_void,
+        "glGetShaderiv" => get_shaderiv as *const c_void,
+        "glGetShaderInfoLog" => get_shader_info_log as *const c_void,
+        "glGenTextures" => gen_textures as *const c_void,
+        "glDeleteTextures" => delete_textures as *const c_void,
+        "glBindTexture" => bind_texture as *const c_void,
+        "glTexImage2D" => tex_image_2d as *const c_void,
+        "glGenerateMipmap" => generate_mipmap as *const c_void,
+        "glTexParameteri" => tex_parameter_i as *const c_void,
+        "glUniform1fv" => uniform_1fv as *const c_void,
+        "glUniform2fv" => uniform_2fv as *const c_void,
+        "glUniform3fv" => uniform_3fv as *const c_void,
+        "glUniform4fv" => uniform_4fv as *const c_void,
+        "glUniform1iv" => uniform_1iv as *const c_void,
+        "glUniform2iv" => uniform_2iv as *const c_void,
+        "glUniform3iv" => uniform_3iv as *const c_void,
+        "glUniform4iv" => uniform_4iv as *const c_void,
+        "glUniformMatrix2fv" => uniform_matrix_2fv as *const c_void,
+        "glUniformMatrix3fv" => uniform_matrix_3fv as *const c_void,
+        "glUniformMatrix4fv" => uniform_matrix_4fv as *const c_void,
+        "glClearColor" => clear_color as *const c_void,
+        "glClear" => clear as *const c_void,
+        _ => std::ptr::null(),
+    }
+}
+
+/// Runs `f` with this thread's mock state, e.g. to set up uniform locations.
+pub fn with<R>(f: impl FnOnce(&mut MockGl) -> R) -> R {
+    MOCK.with_borrow_mut(f)
+}
+
+/// Returns and clears the calls recorded on this thread.
+pub fn take_calls() -> Vec<GlCall> {
+    with(|m| std::mem::take(&mut m.calls))
+}
+
+/// Resets this thread's mock state, including recorded calls.
+pub fn reset() {
+    with(|m| *m = MockGl::default());
+}
+
+fn record(call: GlCall) {
+    with(|m| m.calls.push(call));
+}
+
+/// Copies `len` items from a client pointer, treating null as empty.
+fn copy_slice<T: Copy>(ptr: *const T, len: usize) -> Vec<T> {
+    if ptr.is_null() {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec()
+    }
+}
+
+fn c_str(ptr: *const GLchar) -> String {
+    unsafe { CStr::from_ptr(ptr) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn gen_names(n: GLsizei, names: *mut GLuint) -> Vec<GLuint> {
+    let generated: Vec<GLuint> = with(|m| (0..n).map(|_| m.gen_name()).collect());
+    unsafe { std::ptr::copy_nonoverlapping(generated.as_ptr(), names, generated.len()) };
+    generated
+}
+
+/// Writes `log` the way `glGet*InfoLog` does: truncated to `buf_size` and nul-terminated.
+fn write_info_log(log: &str, buf_size: GLsizei, length: *mut GLsizei, info_log: *mut GLchar) {
+    // Like GL, write nothing at all into an empty buffer.
+    if buf_size <= 0 {
+        if !length.is_null() {
+            unsafe { *length = 0 };
+        }
+        return;
+    }
+    let n = log.len().min((buf_size - 1) as usize);
+    unsafe {
+        std::ptr::copy_nonoverlapping(log.as_ptr(), info_log as *mut u8, n);
+        *info_log.add(n) = 0;
+        if !length.is_null() {
+            *length = n as GLsizei;
+        }
+    }
+}
+
+fn find_location(variables: &[MockVariable], name: &str) -> GLint {
+    variables
+        .iter()
+        .find_map(|v| v.location_of(name))
+        .unwrap_or(-1)
+}
+
+/// Answers `glGetActive*` the way GL does, writing a nul-terminated name.
+#[allow(clippy::too_many_arguments)]
+fn get_active(
+    variables: &[MockVariable],
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    let Some(variable) = variables.get(index as usize) else {
+        return;
+    };
+    write_info_log(&variable.name, buf_size, length, name);
+    unsafe {
+        *size = variable.size;
+        *type_ = variable.type_;
+    }
+}
+
+/// `ACTIVE_*_MAX_LENGTH`: the longest name, including its nul.
+fn max_name_length(variables: &[MockVariable]) -> GLint {
+    variables
+        .iter()
+        .map(|v| v.name.len() as GLint + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+fn info_log_length() -> GLint {
+    with(|m| {
+        if m.info_log.is_empty() {
+            0
+        } else {
+            m.info_log.len() as GLint + 1
+        }
+    })
+}
+
+extern "system" fn gen_buffers(n: GLsizei, buffers: *mut GLuint) {
+    record(GlCall::GenBuffers(gen_names(n, buffers)));
+}
+
+extern "system" fn delete_buffers(n: GLsizei, buffers: *const GLuint) {
+    record(GlCall::DeleteBuffers(copy_slice(buffers, n as usize)));
+}
+
+extern "system" fn bind_buffer(target: GLenum, buffer: GLuint) {
+    record(GlCall::BindBuffer { target, buffer });
+}
+
+extern "system" fn buffer_data(
+    target: GLenum,
+    size: GLsizeiptr,
+    data: *const c_void,
+    usage: GLenum,
+) {
+    let data = (!data.is_null()).then(|| copy_slice(data as *const u8, size as usize));
+    record(GlCall::BufferData {
+        target,
+        data,
+        size,
+        usage,
+    });
+}
+
+extern "system" fn buffer_sub_data(
+    target: GLenum,
+    offset: GLintptr,
+    size: GLsizeiptr,
+    data: *const c_void,
+) {
+    record(GlCall::BufferSubData {
+        target,
+        offset,
+        data: copy_slice(data as *const u8, size as usize),
+    });
+}
+
+extern "system" fn vertex_attrib_divisor(index: GLuint, divisor: GLuint) {
+    record(GlCall::VertexAttribDivisor { index, divisor });
+}
+
+extern "system" fn enable_vertex_attrib_array(index: GLuint) {
+    record(GlCall::EnableVertexAttribArray(index));
+}
+
+extern "system" fn disable_vertex_attrib_array(index: GLuint) {
+    record(GlCall::DisableVertexAttribArray(index));
+}
+
+extern "system" fn vertex_attrib_pointer(
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: *const c_void,
+) {
+    record(GlCall::VertexAttribPointer {
+        index,
+        size,
+        type_,
+        normalized: normalized != gl::FALSE,
+        stride,
+        pointer: pointer as usize,
+    });
+}
+
+extern "system" fn draw_arrays(mode: GLenum, first: GLint, count: GLsizei) {
+    record(GlCall::DrawArrays { mode, first, count });
+}
+
+extern "system" fn draw_elements(
+    mode: GLenum,
+    count: GLsizei,
+    type_: GLenum,
+    indices: *const c_void,
+) {
+    record(GlCall::DrawElements {
+        mode,
+        count,
+        type_,
+        indices: indices as usize,
+    });
+}
+
+extern "system" fn draw_elements_instanced(
+    mode: GLenum,
+    count: GLsizei,
+    type_: GLenum,
+    indices: *const c_void,
+    primcount: GLsizei,
+) {
+    record(GlCall::DrawElementsInstanced {
+        mode,
+        count,
+        type_,
+        indices: indices as usize,
+        primcount,
+    });
+}
+
+extern "system" fn get_error() -> GLenum {
+    with(|m| m.errors.pop_front().unwrap_or(gl::NO_ERROR))
+}
+
+extern "system" fn use_program(program: GLuint) {
+    record(GlCall::UseProgram(program));
+}
+
+extern "system" fn create_program() -> GLuint {
+    let program = with(MockGl::gen_name);
+    record(GlCall::CreateProgram(program));
+    program
+}
+
+extern "system" fn attach_shader(program: GLuint, shader: GLuint) {
+    record(GlCall::AttachShader { program, shader });
+}
+
+extern "system" fn detach_shader(program: GLuint, shader: GLuint) {
+    record(GlCall::DetachShader { program, shader });
+}
+
+extern "system" fn bind_attrib_location(program: GLuint, index: GLuint, name: *const GLchar) {
+    let name = c_str(name);
+    record(GlCall::BindAttribLocation {
+        program,
+        index,
+        name,
+    });
+}
+
+extern "system" fn link_program(program: GLuint) {
+    record(GlCall::LinkProgram(program));
+}
+
+extern "system" fn validate_program(program: GLuint) {
+    record(GlCall::ValidateProgram(program));
+}
+
+extern "system" fn delete_program(program: GLuint) {
+    record(GlCall::DeleteProgram(program));
+}
+
+extern "system" fn get_program_binary(
+    program: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    binary_format: *mut GLenum,
+    binary: *mut c_void,
+) {
+    record(GlCall::GetProgramBinary(program));
+    let data = with(|m| m.program_binary.clone());
+    let len = data.len().min(buf_size.max(0) as usize);
+    unsafe {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), binary as *mut u8, len);
+        if !length.is_null() {
+            *length = len as GLsizei;
+        }
+        *binary_format = MOCK_BINARY_FORMAT;
+    }
+}
+
+extern "system" fn program_binary(
+    program: GLuint,
+    format: GLenum,
+    binary: *const c_void,
+    length: GLsizei,
+) {
+    record(GlCall::ProgramBinary {
+        program,
+        format,
+        binary: copy_slice(binary as *const u8, length as usize),
+    });
+}
+
+extern "system" fn get_programiv(program: GLuint, pname: GLenum, params: *mut GLint) {
+    record(GlCall::GetProgramiv { program, pname });
+    let value = match pname {
+        gl::LINK_STATUS => with(|m| m.link_status as GLint),
+        gl::VALIDATE_STATUS => with(|m| m.validate_status as GLint),
+        gl::INFO_LOG_LENGTH => info_log_length(),
+        gl::ACTIVE_ATTRIBUTES => with(|m| m.attributes.len() as GLint),
+        gl::ACTIVE_ATTRIBUTE_MAX_LENGTH => with(|m| max_name_length(&m.attributes)),
+        gl::ACTIVE_UNIFORMS => with(|m| m.uniforms.len() as GLint),
+        gl::ACTIVE_UNIFORM_MAX_LENGTH => with(|m| max_name_length(&m.uniforms)),
+        gl::PROGRAM_BINARY_LENGTH => with(|m| m.program_binary.len() as GLint),
+        _ => 0,
+    };
+    unsafe { *params = value };
+}
+
+extern "system" fn get_active_attrib(
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    record(GlCall::GetActiveAttrib { program, index });
+    with(|m| get_active(&m.attributes, index, buf_size, length, size, type_, name));
+}
+
+extern "system" fn get_active_uniform(
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    size: *mut GLint,
+    type_: *mut GLenum,
+    name: *mut GLchar,
+) {
+    record(GlCall::GetActiveUniform { program, index });
+    with(|m| get_active(&m.uniforms, index, buf_size, length, size, type_, name));
+}
+
+extern "system" fn get_program_info_log(
+    program: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    record(GlCall::GetProgramInfoLog(program));
+    let log = with(|m| m.info_log.clone());
+    write_info_log(&log, buf_size, length, info_log);
+}
+
+extern "system" fn get_attrib_location(program: GLuint, name: *const GLchar) -> GLint {
+    let name = c_str(name);
+    let location = with(|m| find_location(&m.attributes, &name));
+    record(GlCall::GetAttribLocation { program, name });
+    location
+}
+
+extern "system" fn get_uniform_location(program: GLuint, name: *const GLchar) -> GLint {
+    let name = c_str(name);
+    let location = with(|m| find_location(&m.uniforms, &name));
+    record(GlCall::GetUniformLocation { program, name });
+    location
+}
+
+extern "system" fn create_shader(type_: GLenum) -> GLuint {
+    let shader = with(|m| {
+        let shader = m.gen_name();
+        m.shader_types.insert(shader, type_);
+        shader
+    });
+    record(GlCall::CreateShader { type_, shader });
+    shader
+}
+
+extern "system" fn shader_source(
+    shader: GLuint,
+    count: GLsizei,
+    string: *const *const GLchar,
+    length: *const GLint,
+) {
+    let mut source = String::new();
+    for i in 0..count as usize {
+        let part = unsafe { *string.add(i) };
+        let len = if length.is_null() {
+            -1
+        } else {
+            unsafe { *length.add(i) }
+        };
+        if len < 0 {
+            source.push_str(&c_str(part));
+        } else {
+            source.push_str(&String::from_utf8_lossy(&copy_slice(
+                part as *const u8,
+                len as usize,
+            )));
+        }
+    }
+    record(GlCall::ShaderSource { shader, source });
+}
+
+extern "system" fn shader_binary(
+    count: GLsizei,
+    shaders: *const GLuint,
+    format: GLenum,
+    binary: *const c_void,
+    length: GLsizei,
+) {
+    record(GlCall::ShaderBinary {
+        shaders: copy_slice(shaders, count as usize),
+        format,
+        binary: copy_slice(binary as *const u8, length as usize),
+    });
+    with(|m| {
+        let errors = m.shader_binary_errors.clone();
+        m.errors.extend(errors);
+    });
+}
+
+extern "system" fn compile_shader(shader: GLuint) {
+    record(GlCall::CompileShader(shader));
+}
+
+extern "system" fn delete_shader(shader: GLuint) {
+    record(GlCall::DeleteShader(shader));
+}
+
+extern "system" fn get_shaderiv(shader: GLuint, pname: GLenum, params: *mut GLint) {
+    record(GlCall::GetShaderiv { shader, pname });
+    let value = match pname {
+        gl::COMPILE_STATUS => with(|m| m.compile_status as GLint),
+        gl::INFO_LOG_LENGTH => info_log_length(),
+        gl::SHADER_TYPE => with(|m| m.shader_types.get(&shader).copied().unwrap_or(0) as GLint),
+        _ => 0,
+    };
+    unsafe { *params = value };
+}
+
+extern "system" fn get_shader_info_log(
+    shader: GLuint,
+    buf_size: GLsizei,
+    length: *mut GLsizei,
+    info_log: *mut GLchar,
+) {
+    record(GlCall::GetShaderInfoLog(shader));
+    let log = with(|m| m.info_log.clone());
+    write_info_log(&log, buf_size, length, info_log);
+}
+
+extern "system" fn gen_textures(n: GLsizei, textures: *mut GLuint) {
+    record(GlCall::GenTextures(gen_names(n, textures)));
+}
+
+extern "system" fn delete_textures(n: GLsizei, textures: *const GLuint) {
+    record(GlCall::DeleteTextures(copy_slice(textures, n as usize)));
+}
+
+extern "system" fn bind_texture(target: GLenum, texture: GLuint) {
+    record(GlCall::BindTexture { target, texture });
+}
+
+#[allow(clippy::too_many_arguments)]
+extern "system" fn tex_image_2d(
+    target: GLenum,
+    level: GLint,
+    internalformat: GLint,
+    width: GLsizei,
+    height: GLsizei,
+    _border: GLint,
+    format: GLenum,
+    type_: GLenum,
+    pixels: *const c_void,
+) {
+    record(GlCall::TexImage2D {
+        target,
+        level,
+        internalformat,
+        width,
+        height,
+        format,
+        type_,
+        pixels: pixels as usize,
+    });
+}
+
+extern "system" fn generate_mipmap(target: GLenum) {
+    record(GlCall::GenerateMipmap(target));
+}
+
+extern "system" fn tex_parameter_i(target: GLenum, pname: GLenum, param: GLint) {
+    record(GlCall::TexParameteri {
+        target,
+        pname,
+        param,
+    });
+}
+
+macro_rules! mock_uniform {
+    ($name:ident, $variant:ident, $t:ty, $components:expr) => {
+        extern "system" fn $name(location: GLint, count: GLsizei, value: *const $t) {
+            record(GlCall::$variant {
+                components: $components,
+                location,
+                count,
+                value: copy_slice(value, count as usize * $components),
+            });
+        }
+    };
+}
+
+mock_uniform!(uniform_1fv, Uniformfv, GLfloat, 1);
+mock_uniform!(uniform_2fv, Uniformfv, GLfloat, 2);
+mock_uniform!(uniform_3fv, Uniformfv, GLfloat, 3);
+mock_uniform!(uniform_4fv, Uniformfv, GLfloat, 4);
+mock_uniform!(uniform_1iv, Uniformiv, GLint, 1);
+mock_uniform!(uniform_2iv, Uniformiv, GLint, 2);
+mock_uniform!(uniform_3iv, Uniformiv, GLint, 3);
+mock_uniform!(uniform_4iv, Uniformiv, GLint, 4);
+
+macro_rules! mock_uniform_matrix {
+    ($name:ident, $dim:expr) => {
+        extern "system" fn $name(
+            location: GLint,
+            count: GLsizei,
+            transpose: GLboolean,
+            value: *const GLfloat,
+        ) {
+            record(GlCall::UniformMatrixfv {
+                dim: $dim,
+                location,
+                count,
+                transpose: transpose != gl::FALSE,
+                value: copy_slice(value, count as usize * $dim * $dim),
+            });
+        }
+    };
+}
+
+mock_uniform_matrix!(uniform_matrix_2fv, 2);
+mock_uniform_matrix!(uniform_matrix_3fv, 3);
+mock_uniform_matrix!(uniform_matrix_4fv, 4);
+
+extern "system" fn clear_color(red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+    record(GlCall::ClearColor([red, green, blue, alpha]));
+}
+
+extern "system" fn clear(mask: GLenum) {
+    record(GlCall::Clear(mask));
+}
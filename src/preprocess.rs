@@ -0,0 +1,426 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    GlContext,
+    shader::{Shader, ShaderError, ShaderStage, load_shader_named},
+};
+
+/// Finds the text of `#include`d files.
+pub trait IncludeResolver {
+    /// Returns the text of `name`, as included from the file `from`.
+    /// `from` is `None` for the root file passed to [`ShaderSourceBuilder::build`].
+    fn resolve(&self, name: &str, from: Option<&str>) -> Option<String>;
+    /// The name `name` goes by once included from `from`: what its own includes get as
+    /// `from`, and what errors and the [`LineMap`] call it. `name` as written by default.
+    fn include_name(&self, name: &str, _from: Option<&str>) -> String {
+        name.to_string()
+    }
+}
+
+impl<R: IncludeResolver + ?Sized> IncludeResolver for &R {
+    fn resolve(&self, name: &str, from: Option<&str>) -> Option<String> {
+        (**self).resolve(name, from)
+    }
+    fn include_name(&self, name: &str, from: Option<&str>) -> String {
+        (**self).include_name(name, from)
+    }
+}
+
+/// Resolves includes from sources compiled into the program, e.g. with `include_str!`.
+#[derive(Default, Clone)]
+pub struct EmbeddedResolver(HashMap<String, String>);
+
+impl EmbeddedResolver {
+    pub fn new<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        EmbeddedResolver(
+            files
+                .into_iter()
+                .map(|(name, text)| (name.to_string(), text.to_string()))
+                .collect(),
+        )
+    }
+    pub fn insert(&mut self, name: &str, text: &str) {
+        self.0.insert(name.to_string(), text.to_string());
+    }
+}
+
+impl IncludeResolver for EmbeddedResolver {
+    fn resolve(&self, name: &str, _from: Option<&str>) -> Option<String> {
+        self.0.get(name).cloned()
+    }
+}
+
+/// Resolves includes from the filesystem, under `root`: next to the including file first,
+/// then at `root` itself. Names are paths relative to `root`, including in errors and the
+/// [`LineMap`].
+///
+/// Absolute names, and names whose `..` climb above `root`, are never found.
+#[derive(Clone)]
+pub struct FsResolver {
+    pub root: PathBuf,
+}
+
+impl FsResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsResolver { root: root.into() }
+    }
+    /// Where `name` may be, relative to `root`, in the order they are tried.
+    fn candidates(name: &str, from: Option<&str>) -> impl Iterator<Item = PathBuf> {
+        let beside = from
+            .and_then(|from| Path::new(from).parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.join(name));
+        beside
+            .into_iter()
+            .chain([PathBuf::from(name)])
+            .filter_map(|path| Self::normalize(&path))
+    }
+    /// `path` with `.` and `..` resolved, or `None` if it isn't under `root`.
+    fn normalize(path: &Path) -> Option<PathBuf> {
+        let mut normal = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => normal.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !normal.pop() {
+                        return None;
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        Some(normal)
+    }
+}
+
+impl IncludeResolver for FsResolver {
+    fn resolve(&self, name: &str, from: Option<&str>) -> Option<String> {
+        Self::candidates(name, from)
+            .find_map(|path| std::fs::read_to_string(self.root.join(path)).ok())
+    }
+    fn include_name(&self, name: &str, from: Option<&str>) -> String {
+        Self::candidates(name, from)
+            .find(|path| self.root.join(path).is_file())
+            .map_or_else(
+                || name.to_string(),
+                |path| path.to_string_lossy().into_owned(),
+            )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessError {
+    NotFound {
+        name: String,
+        /// The including file and line, or `None` for the root file.
+        from: Option<(String, u32)>,
+    },
+    /// The chain of includes that leads back to its start.
+    Cycle(Vec<String>),
+}
+
+impl std::fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreprocessError::NotFound { name, from: None } => {
+                write!(f, "Shader source \"{name}\" not found")
+            }
+            PreprocessError::NotFound {
+                name,
+                from: Some((file, line)),
+            } => write!(f, "{file}:{line}: included file \"{name}\" not found"),
+            PreprocessError::Cycle(chain) => {
+                write!(f, "Include cycle: {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Where each line of a preprocessed source came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineMap(Vec<(String, u32)>);
+
+impl LineMap {
+    /// The original file and 1-based line of the 1-based `line` of the output.
+    /// `#define`s from the builder come from the file `<defines>`.
+    pub fn lookup(&self, line: u32) -> Option<(&str, u32)> {
+        let (file, line) = self.0.get((line as usize).checked_sub(1)?)?;
+        Some((file, *line))
+    }
+}
+
+/// A preprocessed shader source, ready for [`ShaderSource::compile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderSource {
+    /// The name of the root file.
+    pub name: String,
+    pub text: String,
+    pub line_map: LineMap,
+}
+
+impl ShaderSource {
+    /// Compiles the source, pointing any errors back at the original files and lines.
+    pub fn compile(&self, ctx: &GlContext, stage: ShaderStage) -> Result<Shader, ShaderError> {
+        load_shader_named(ctx, &self.text, stage, &self.name).map_err(|e| match e {
+            ShaderError::Compile(mut diagnostic) => {
+                diagnostic.remap(&self.line_map);
+                ShaderError::Compile(diagnostic)
+            }
+            e => e,
+        })
+    }
+}
+
+/// Builds a [`ShaderSource`] by resolving `#include "file"` lines and prepending `#define`s.
+///
+/// ```rust
+/// use vita_gl_helpers::preprocess::{EmbeddedResolver, ShaderSourceBuilder};
+/// let resolver = EmbeddedResolver::new([
+///     ("common.cg", "#pragma once\nfloat4 tint(float4 c) { return c * TINT; }"),
+///     ("frag.cg", "#include \"common.cg\"\nfloat4 main(float4 c : COLOR0) : COLOR { return tint(c); }"),
+/// ]);
+/// let source = ShaderSourceBuilder::new(resolver)
+///     .define("TINT", "0.5")
+///     .build("frag.cg")
+///     .unwrap();
+/// assert_eq!(source.line_map.lookup(3), Some(("common.cg", 2)));
+/// assert_eq!(source.line_map.lookup(4), Some(("frag.cg", 2)));
+/// ```
+pub struct ShaderSourceBuilder<R: IncludeResolver> {
+    resolver: R,
+    defines: Vec<(String, String)>,
+}
+
+impl<R: IncludeResolver> ShaderSourceBuilder<R> {
+    pub fn new(resolver: R) -> Self {
+        ShaderSourceBuilder {
+            resolver,
+            defines: Vec::new(),
+        }
+    }
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+    /// Defines `name` as `1`.
+    pub fn define_flag(self, name: impl Into<String>) -> Self {
+        self.define(name, "1")
+    }
+    pub fn build(&self, root: &str) -> Result<ShaderSource, PreprocessError> {
+        let mut out = Expansion::default();
+        for (i, (name, value)) in self.defines.iter().enumerate() {
+            out.push_line(
+                &format!("#define {name} {value}"),
+                "<defines>",
+                i as u32 + 1,
+            );
+        }
+        let text = self
+            .resolver
+            .resolve(root, None)
+            .ok_or_else(|| PreprocessError::NotFound {
+                name: root.to_string(),
+                from: None,
+            })?;
+        self.expand(root, &text, &mut Vec::new(), &mut out)?;
+        Ok(ShaderSource {
+            name: root.to_string(),
+            text: out.text,
+            line_map: LineMap(out.lines),
+        })
+    }
+    fn expand(
+        &self,
+        name: &str,
+        text: &str,
+        stack: &mut Vec<String>,
+        out: &mut Expansion,
+    ) -> Result<(), PreprocessError> {
+        if stack.iter().any(|s| s == name) {
+            let mut chain = stack.clone();
+            chain.push(name.to_string());
+            return Err(PreprocessError::Cycle(chain));
+        }
+        if out.once.contains(name) {
+            return Ok(());
+        }
+        stack.push(name.to_string());
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i as u32 + 1;
+            match directive(line) {
+                Some(Directive::Include(include)) => {
+                    let text = self.resolver.resolve(include, Some(name)).ok_or_else(|| {
+                        PreprocessError::NotFound {
+                            name: include.to_string(),
+                            from: Some((name.to_string(), line_no)),
+                        }
+                    })?;
+                    let include = self.resolver.include_name(include, Some(name));
+                    self.expand(&include, &text, stack, out)?;
+                }
+                Some(Directive::PragmaOnce) => {
+                    out.once.insert(name.to_string());
+                    // Keep the line so the output still lines up with the map.
+                    out.push_line("", name, line_no);
+                }
+                None => out.push_line(line, name, line_no),
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Expansion {
+    text: String,
+    lines: Vec<(String, u32)>,
+    /// Files that said `#pragma once` and have been included.
+    once: HashSet<String>,
+}
+
+impl Expansion {
+    fn push_line(&mut self, line: &str, file: &str, line_no: u32) {
+        self.text.push_str(line);
+        self.text.push('\n');
+        self.lines.push((file.to_string(), line_no));
+    }
+}
+
+enum Directive<'a> {
+    Include(&'a str),
+    PragmaOnce,
+}
+
+fn directive(line: &str) -> Option<Directive<'_>> {
+    let rest = line.trim().strip_prefix('#')?.trim_start();
+    if let Some(rest) = rest.strip_prefix("include") {
+        let rest = rest.trim();
+        let name = rest
+            .strip_prefix('"')
+            .and_then(|r| r.strip_suffix('"'))
+            .or_else(|| rest.strip_prefix('<').and_then(|r| r.strip_suffix('>')))?;
+        return Some(Directive::Include(name));
+    }
+    let rest = rest.strip_prefix("pragma")?;
+    (rest.split_whitespace().collect::<Vec<_>>() == ["once"]).then_some(Directive::PragmaOnce)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, removed on drop.
+    pub(crate) struct TempDir(pub(crate) PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "vita_gl_helpers_preprocess_{}_{:?}",
+                std::process::id(),
+                std::thread::current().id()
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            for (name, text) in files {
+                let path = dir.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, text).unwrap();
+            }
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn fs_resolver_nested_includes() {
+        let dir = TempDir::new(&[
+            ("main.cg", "#include \"lib/a.cg\"\nmain"),
+            ("lib/a.cg", "#include \"b.cg\"\na"),
+            ("lib/b.cg", "#include \"c.cg\"\nb"),
+            ("lib/c.cg", "c"),
+        ]);
+        let source = ShaderSourceBuilder::new(FsResolver::new(&dir.0))
+            .build("main.cg")
+            .unwrap();
+        assert_eq!(source.text, "c\nb\na\nmain\n");
+        assert_eq!(source.line_map.lookup(1), Some(("lib/c.cg", 1)));
+        assert_eq!(source.line_map.lookup(2), Some(("lib/b.cg", 2)));
+    }
+
+    #[test]
+    fn fs_resolver_stays_under_root() {
+        let dir = TempDir::new(&[("main.cg", "#include \"Cargo.toml\"")]);
+        // Cargo.toml is in the working directory, but not under the root.
+        let error = ShaderSourceBuilder::new(FsResolver::new(&dir.0))
+            .build("main.cg")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PreprocessError::NotFound {
+                name: "Cargo.toml".to_string(),
+                from: Some(("main.cg".to_string(), 1)),
+            }
+        );
+    }
+
+    #[test]
+    fn fs_resolver_rejects_parent_dirs() {
+        let dir = TempDir::new(&[
+            ("secret.cg", "secret"),
+            (
+                "shaders/main.cg",
+                "#include \"lib/a.cg\"\n#include \"../secret.cg\"",
+            ),
+            ("shaders/lib/a.cg", "#include \"../common.cg\""),
+            ("shaders/common.cg", "common"),
+        ]);
+        let error = ShaderSourceBuilder::new(FsResolver::new(dir.0.join("shaders")))
+            .build("main.cg")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PreprocessError::NotFound {
+                name: "../secret.cg".to_string(),
+                from: Some(("main.cg".to_string(), 2)),
+            }
+        );
+        // `..` that stays under the root is fine.
+        let source = ShaderSourceBuilder::new(FsResolver::new(dir.0.join("shaders")))
+            .build("lib/a.cg")
+            .unwrap();
+        assert_eq!(source.text, "common\n");
+        assert_eq!(source.line_map.lookup(1), Some(("common.cg", 1)));
+    }
+
+    #[test]
+    fn fs_resolver_rejects_absolute_paths() {
+        let dir = TempDir::new(&[("secret.cg", "secret"), ("shaders/main.cg", "")]);
+        let secret = dir.0.join("secret.cg").to_string_lossy().into_owned();
+        std::fs::write(
+            dir.0.join("shaders/main.cg"),
+            format!("#include \"{secret}\""),
+        )
+        .unwrap();
+        let error = ShaderSourceBuilder::new(FsResolver::new(dir.0.join("shaders")))
+            .build("main.cg")
+            .unwrap_err();
+        assert_eq!(
+            error,
+            PreprocessError::NotFound {
+                name: secret,
+                from: Some(("main.cg".to_string(), 1)),
+            }
+        );
+    }
+}
@@ -1,43 +1,68 @@
+use std::{marker::PhantomData, ops::Deref};
+
 use derive_more::{From, Into};
 
 use crate::{
+    GlContext,
     attribute::{AttributeTable, MissingAttributes},
+    context::NotSend,
+    errors::check_wrapper,
+    logging,
     shader::Shader,
     uniforms::{MissingUniforms, UniformTable},
 };
 
 #[derive(Debug, Clone)]
 pub enum ProgramError {
+    /// `glCreateProgram` failed.
     NoProgram,
-    String(String),
+    /// Linking failed, with the info log.
+    Link(String),
+    /// A [`ProgramBuilder`] was linked without any shaders.
+    NoShaders,
+    /// `glBindAttribLocation` can't take this name: it contains a NUL or starts with `gl_`.
+    BadAttribName(String),
+    /// `glValidateProgram` failed, with the info log.
+    Validate(String),
 }
 
 impl std::fmt::Display for ProgramError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ProgramError::NoProgram => write!(f, "No Program"),
-            ProgramError::String(s) => write!(f, "Program did not link:\n{}", s),
+            ProgramError::Link(s) => write!(f, "Program did not link:\n{}", s),
+            ProgramError::NoShaders => write!(f, "Program has no shaders to link"),
+            ProgramError::BadAttribName(name) => {
+                write!(f, "Can't bind attribute location of {name:?}")
+            }
+            ProgramError::Validate(s) => write!(f, "Program did not validate:\n{}", s),
         }
     }
 }
 
 impl std::error::Error for ProgramError {}
 
-///Make sure to call `.delete()` when you are done.
+///Make sure to call `.delete()` when you are done, or wrap it in an [`OwnedProgram`].
 #[derive(From, Into, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Program(gl::types::GLuint);
 
 impl Program {
-    pub fn use_me(&self) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn use_me(&self, _ctx: &GlContext) {
         unsafe {
             gl::UseProgram(self.0);
         }
+        check_wrapper!("Program::use_me", self.0);
     }
+    /// # Safety
+    /// `param` must be a valid `glGetProgramiv` parameter.
     pub unsafe fn get_iv(&self, param: gl::types::GLenum) -> i32 {
         let mut var = 0;
         unsafe { gl::GetProgramiv(self.0, param, &mut var) };
         var
     }
+    /// # Safety
+    /// The program must not have been deleted.
     pub unsafe fn get_info_log(&self) -> String {
         let info_len = unsafe { self.get_iv(gl::INFO_LOG_LENGTH) };
         let mut info_log = vec![0u8; info_len as usize];
@@ -51,54 +76,413 @@ impl Program {
         };
         unsafe { String::from_utf8_unchecked(info_log) }
     }
+    /// # Safety
+    /// The program must not be used after this.
+    #[cfg_attr(gl_check, track_caller)]
     pub unsafe fn delete(&self) {
         unsafe { gl::DeleteProgram(self.0) }
+        check_wrapper!("Program::delete", self.0);
     }
-    pub fn get_attrib_location(&self, attrib: &str) -> i32 {
-        unsafe {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn get_attrib_location(&self, _ctx: &GlContext, attrib: &str) -> i32 {
+        let location = unsafe {
             gl::GetAttribLocation(
                 self.0,
                 std::ffi::CString::new(attrib)
                     .expect("What the hell")
                     .as_ptr() as _,
             )
-        }
+        };
+        check_wrapper!("Program::get_attrib_location", self.0, attrib);
+        location
     }
-    pub fn get_uniform_location(&self, uniform: &str) -> i32 {
-        unsafe {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn get_uniform_location(&self, _ctx: &GlContext, uniform: &str) -> i32 {
+        let location = unsafe {
             gl::GetUniformLocation(
                 self.0,
                 std::ffi::CString::new(uniform)
                     .expect("What the hell")
                     .as_ptr() as _,
             )
+        };
+        check_wrapper!("Program::get_uniform_location", self.0, uniform);
+        location
+    }
+    pub fn get_uniform_table<T: UniformTable>(
+        &self,
+        ctx: &GlContext,
+    ) -> Result<T, MissingUniforms> {
+        T::with_locations_from(ctx, self)
+    }
+    pub fn get_attribute_table<T: AttributeTable>(
+        &self,
+        ctx: &GlContext,
+    ) -> Result<T, MissingAttributes> {
+        T::with_locations_from(ctx, self)
+    }
+}
+
+/// The result of looking up every entry of an attribute or uniform table.
+///
+/// Missing entries are left at location -1, so using them does nothing.
+pub struct TableLookup<T> {
+    pub table: T,
+    /// Entries the shader doesn't have that the table requires.
+    pub missing_required: Vec<&'static str>,
+    /// Entries the shader doesn't have that the table marked optional.
+    pub missing_optional: Vec<&'static str>,
+}
+
+impl<T> TableLookup<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> TableLookup<U> {
+        TableLookup {
+            table: f(self.table),
+            missing_required: self.missing_required,
+            missing_optional: self.missing_optional,
         }
     }
-    pub fn get_uniform_table<T: UniformTable>(&self) -> Result<T, MissingUniforms> {
-        T::with_locations_from(self)
+    /// Fails if any required entry is missing.
+    pub fn required<E: From<Vec<&'static str>>>(self) -> Result<T, E> {
+        if self.missing_required.is_empty() {
+            Ok(self.table)
+        } else {
+            Err(self.missing_required.into())
+        }
     }
-    pub fn get_attribute_table<T: AttributeTable>(&self) -> Result<T, MissingAttributes> {
-        T::with_locations_from(self)
+    /// Fails if any entry at all is missing, listing the required ones first.
+    pub fn strict<E: From<Vec<&'static str>>>(mut self) -> Result<T, E> {
+        if self.missing_required.is_empty() && self.missing_optional.is_empty() {
+            Ok(self.table)
+        } else {
+            self.missing_required.append(&mut self.missing_optional);
+            Err(self.missing_required.into())
+        }
     }
 }
 
-pub fn link_program(vert: Shader, frag: Shader) -> Result<Program, ProgramError> {
-    println!("Linking Program...");
-    let program = unsafe { gl::CreateProgram() };
-    if program == 0 {
-        return Err(ProgramError::NoProgram);
-    }
-    unsafe {
-        gl::AttachShader(program, vert.into());
-        gl::AttachShader(program, frag.into());
-        gl::LinkProgram(program);
-        let program = Program::from(program);
-        let linked = program.get_iv(gl::LINK_STATUS);
-        if linked == 0 {
-            let info_log = program.get_info_log();
-            program.delete();
-            return Err(ProgramError::String(info_log));
+/// Looks up each `(name, optional)` entry with `get_location`. Used by the table macros.
+#[doc(hidden)]
+pub fn lookup_locations<const N: usize>(
+    entries: [(&'static str, bool); N],
+    get_location: impl Fn(&str) -> i32,
+) -> TableLookup<[i32; N]> {
+    let mut missing_required = Vec::new();
+    let mut missing_optional = Vec::new();
+    let table = entries.map(|(name, optional)| {
+        let location = get_location(name);
+        if location < 0 {
+            if optional {
+                missing_optional.push(name);
+            } else {
+                missing_required.push(name);
+            }
         }
-        Ok(program)
+        location
+    });
+    TableLookup {
+        table,
+        missing_required,
+        missing_optional,
+    }
+}
+
+#[cfg_attr(gl_check, track_caller)]
+pub fn link_program(ctx: &GlContext, vert: Shader, frag: Shader) -> Result<Program, ProgramError> {
+    ProgramBuilder::new().shader(vert).shader(frag).link(ctx)
+}
+
+/// Links a program from any number of shaders, with optional steps around the link.
+///
+/// ```no_run
+/// # use vita_gl_helpers::{program::ProgramBuilder, shader::Shader};
+/// # let ctx = vita_gl_helpers::initialise_default().unwrap();
+/// # let (vert, frag) = (Shader::default(), Shader::default());
+/// let program = ProgramBuilder::new()
+///     .shaders([vert, frag])
+///     .bind_attrib_location("aPos", 0)
+///     .bind_attrib_location("aColor", 1)
+///     .delete_shaders(true)
+///     .link(&ctx)
+///     .unwrap();
+/// ```
+#[derive(Clone, Default)]
+pub struct ProgramBuilder {
+    shaders: Vec<Shader>,
+    attrib_locations: Vec<(String, u32)>,
+    delete_shaders: bool,
+    validate: bool,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn shader(mut self, shader: Shader) -> Self {
+        self.shaders.push(shader);
+        self
+    }
+    pub fn shaders(mut self, shaders: impl IntoIterator<Item = Shader>) -> Self {
+        self.shaders.extend(shaders);
+        self
+    }
+    /// Binds `name` to `location` before linking, with `glBindAttribLocation`.
+    pub fn bind_attrib_location(mut self, name: impl Into<String>, location: u32) -> Self {
+        self.attrib_locations.push((name.into(), location));
+        self
+    }
+    /// Detaches and deletes the shaders once the program links. Off by default; don't
+    /// turn it on for shaders held by an [`OwnedShader`](crate::shader::OwnedShader).
+    pub fn delete_shaders(mut self, delete: bool) -> Self {
+        self.delete_shaders = delete;
+        self
+    }
+    /// Runs `glValidateProgram` after linking. Off by default, since it checks the
+    /// program against the current GL state, which is rarely final at load time.
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+    /// Creates and links the program. On failure the program is deleted, and the shaders
+    /// are left alone.
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn link(&self, ctx: &GlContext) -> Result<Program, ProgramError> {
+        logging::debug!("Linking program from {} shaders", self.shaders.len());
+        if self.shaders.is_empty() {
+            return Err(ProgramError::NoShaders);
+        }
+        let names = self
+            .attrib_locations
+            .iter()
+            .map(
+                |(name, location)| match std::ffi::CString::new(name.as_str()) {
+                    Ok(c_name) if !name.starts_with("gl_") => Ok((c_name, *location)),
+                    _ => Err(ProgramError::BadAttribName(name.clone())),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = self.create_and_link(ctx, &names);
+        check_wrapper!(
+            "ProgramBuilder::link",
+            self.shaders
+                .iter()
+                .map(|&s| u32::from(s))
+                .collect::<Vec<_>>()
+        );
+        result
+    }
+    /// Calls GL directly, leaving any errors for [`link`](Self::link) to report.
+    fn create_and_link(
+        &self,
+        _ctx: &GlContext,
+        names: &[(std::ffi::CString, u32)],
+    ) -> Result<Program, ProgramError> {
+        let program = unsafe { gl::CreateProgram() };
+        if program == 0 {
+            return Err(ProgramError::NoProgram);
+        }
+        unsafe {
+            for shader in &self.shaders {
+                gl::AttachShader(program, (*shader).into());
+            }
+            for (name, location) in names {
+                gl::BindAttribLocation(program, *location, name.as_ptr() as _);
+            }
+            gl::LinkProgram(program);
+            let program = Program::from(program);
+            if program.get_iv(gl::LINK_STATUS) == 0 {
+                let info_log = program.get_info_log();
+                gl::DeleteProgram(program.into());
+                return Err(ProgramError::Link(info_log));
+            }
+            if self.validate {
+                gl::ValidateProgram(program.into());
+                if program.get_iv(gl::VALIDATE_STATUS) == 0 {
+                    let info_log = program.get_info_log();
+                    gl::DeleteProgram(program.into());
+                    return Err(ProgramError::Validate(info_log));
+                }
+            }
+            if self.delete_shaders {
+                for shader in &self.shaders {
+                    gl::DetachShader(program.into(), (*shader).into());
+                    gl::DeleteShader((*shader).into());
+                }
+            }
+            Ok(program)
+        }
+    }
+}
+
+/// A [`Program`] that is deleted when dropped. Derefs to the [`Program`] handle.
+///
+/// `!Send`, so it is dropped on the GL thread.
+#[derive(PartialEq, Eq)]
+pub struct OwnedProgram(Program, NotSend);
+
+impl OwnedProgram {
+    /// Takes ownership of `program`, which will be deleted when this is dropped.
+    pub fn from_raw(_ctx: &GlContext, program: Program) -> Self {
+        OwnedProgram(program, PhantomData)
+    }
+    /// Gives up ownership without deleting the program.
+    pub fn into_raw(self) -> Program {
+        let program = self.0;
+        std::mem::forget(self);
+        program
+    }
+}
+
+impl Deref for OwnedProgram {
+    type Target = Program;
+
+    fn deref(&self) -> &Program {
+        &self.0
+    }
+}
+
+impl Drop for OwnedProgram {
+    fn drop(&mut self) {
+        unsafe { self.0.delete() }
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, GlCall};
+
+    fn builder() -> ProgramBuilder {
+        ProgramBuilder::new().shaders([Shader::from(10), Shader::from(11)])
+    }
+
+    #[test]
+    fn binds_attributes_before_linking() {
+        let ctx = mock::install();
+        mock::reset();
+        let program = builder()
+            .bind_attrib_location("aPos", 0)
+            .bind_attrib_location("aColor", 3)
+            .delete_shaders(true)
+            .link(&ctx)
+            .ok()
+            .unwrap();
+        assert_eq!(u32::from(program), 1);
+        let calls = mock::take_calls();
+        assert_eq!(
+            calls[..5],
+            [
+                GlCall::CreateProgram(1),
+                GlCall::AttachShader {
+                    program: 1,
+                    shader: 10
+                },
+                GlCall::AttachShader {
+                    program: 1,
+                    shader: 11
+                },
+                GlCall::BindAttribLocation {
+                    program: 1,
+                    index: 0,
+                    name: "aPos".into()
+                },
+                GlCall::BindAttribLocation {
+                    program: 1,
+                    index: 3,
+                    name: "aColor".into()
+                },
+            ]
+        );
+        assert_eq!(calls[5], GlCall::LinkProgram(1));
+        assert_eq!(
+            calls[calls.len() - 4..],
+            [
+                GlCall::DetachShader {
+                    program: 1,
+                    shader: 10
+                },
+                GlCall::DeleteShader(10),
+                GlCall::DetachShader {
+                    program: 1,
+                    shader: 11
+                },
+                GlCall::DeleteShader(11),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_link_keeps_shaders() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| {
+            m.link_status = false;
+            m.info_log = "undefined varying vColor".into();
+        });
+        let error = builder().delete_shaders(true).link(&ctx).err().unwrap();
+        assert!(matches!(error, ProgramError::Link(ref log) if log.contains("vColor")));
+        let calls = mock::take_calls();
+        assert_eq!(calls.last(), Some(&GlCall::DeleteProgram(1)));
+        assert!(
+            !calls
+                .iter()
+                .any(|call| matches!(call, GlCall::DetachShader { .. } | GlCall::DeleteShader(_)))
+        );
+    }
+
+    #[test]
+    fn failed_validate_deletes_program() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| {
+            m.validate_status = false;
+            m.info_log = "sampler mismatch".into();
+        });
+        let error = builder()
+            .validate(true)
+            .delete_shaders(true)
+            .link(&ctx)
+            .err()
+            .unwrap();
+        assert!(matches!(error, ProgramError::Validate(ref log) if log.contains("sampler")));
+        let calls = mock::take_calls();
+        assert!(calls.contains(&GlCall::ValidateProgram(1)));
+        assert_eq!(calls.last(), Some(&GlCall::DeleteProgram(1)));
+        assert!(!calls.contains(&GlCall::DeleteShader(10)));
+    }
+
+    #[test]
+    fn rejected_before_any_gl_call() {
+        let ctx = mock::install();
+        mock::reset();
+        let error = ProgramBuilder::new().link(&ctx).err().unwrap();
+        assert!(matches!(error, ProgramError::NoShaders));
+        for name in ["gl_Position", "a\0b"] {
+            let error = builder()
+                .bind_attrib_location(name, 0)
+                .link(&ctx)
+                .err()
+                .unwrap();
+            assert!(matches!(error, ProgramError::BadAttribName(ref n) if n == name));
+        }
+        assert!(mock::take_calls().is_empty());
+    }
+
+    #[cfg(gl_check)]
+    #[test]
+    fn failed_link_reports_errors() {
+        let ctx = crate::errors::tests::install();
+        mock::with(|m| {
+            m.link_status = false;
+            m.errors.push_back(gl::INVALID_OPERATION);
+        });
+        let line = line!() + 1;
+        let error = builder().link(&ctx).err().unwrap();
+        assert!(matches!(error, ProgramError::Link(_)));
+        let reports = crate::errors::tests::take_reports();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].call, "ProgramBuilder::link");
+        assert_eq!(reports[0].args, "[10, 11]");
+        assert_eq!((reports[0].file, reports[0].line), (file!(), line));
     }
 }
@@ -0,0 +1,290 @@
+use gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use crate::{GlContext, program::Program};
+
+/// The type of an active attribute or uniform, as reported by GL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlType {
+    Float,
+    FloatVec2,
+    FloatVec3,
+    FloatVec4,
+    Int,
+    IntVec2,
+    IntVec3,
+    IntVec4,
+    Bool,
+    BoolVec2,
+    BoolVec3,
+    BoolVec4,
+    FloatMat2,
+    FloatMat3,
+    FloatMat4,
+    Sampler2D,
+    SamplerCube,
+    Other(GLenum),
+}
+
+impl From<GLenum> for GlType {
+    fn from(value: GLenum) -> Self {
+        match value {
+            gl::FLOAT => GlType::Float,
+            gl::FLOAT_VEC2 => GlType::FloatVec2,
+            gl::FLOAT_VEC3 => GlType::FloatVec3,
+            gl::FLOAT_VEC4 => GlType::FloatVec4,
+            gl::INT => GlType::Int,
+            gl::INT_VEC2 => GlType::IntVec2,
+            gl::INT_VEC3 => GlType::IntVec3,
+            gl::INT_VEC4 => GlType::IntVec4,
+            gl::BOOL => GlType::Bool,
+            gl::BOOL_VEC2 => GlType::BoolVec2,
+            gl::BOOL_VEC3 => GlType::BoolVec3,
+            gl::BOOL_VEC4 => GlType::BoolVec4,
+            gl::FLOAT_MAT2 => GlType::FloatMat2,
+            gl::FLOAT_MAT3 => GlType::FloatMat3,
+            gl::FLOAT_MAT4 => GlType::FloatMat4,
+            gl::SAMPLER_2D => GlType::Sampler2D,
+            gl::SAMPLER_CUBE => GlType::SamplerCube,
+            other => GlType::Other(other),
+        }
+    }
+}
+
+/// Shown with its Cg name, e.g. `float2`.
+impl std::fmt::Display for GlType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            GlType::Float => "float",
+            GlType::FloatVec2 => "float2",
+            GlType::FloatVec3 => "float3",
+            GlType::FloatVec4 => "float4",
+            GlType::Int => "int",
+            GlType::IntVec2 => "int2",
+            GlType::IntVec3 => "int3",
+            GlType::IntVec4 => "int4",
+            GlType::Bool => "bool",
+            GlType::BoolVec2 => "bool2",
+            GlType::BoolVec3 => "bool3",
+            GlType::BoolVec4 => "bool4",
+            GlType::FloatMat2 => "float2x2",
+            GlType::FloatMat3 => "float3x3",
+            GlType::FloatMat4 => "float4x4",
+            GlType::Sampler2D => "sampler2D",
+            GlType::SamplerCube => "samplerCUBE",
+            GlType::Other(other) => return write!(f, "<type {other:#x}>"),
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// An active attribute or uniform of a linked program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveVariable {
+    /// As GL reports it, so arrays may end in `[0]`.
+    pub name: String,
+    pub type_: GlType,
+    /// Array length, 1 for non-arrays.
+    pub size: GLint,
+    pub location: GLint,
+}
+
+impl ActiveVariable {
+    /// The name without any trailing `[0]`, as it would be written in a table.
+    pub fn base_name(&self) -> &str {
+        self.name.strip_suffix("[0]").unwrap_or(&self.name)
+    }
+}
+
+impl std::fmt::Display for ActiveVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.type_, self.base_name())?;
+        if self.size != 1 {
+            write!(f, "[{}]", self.size)?;
+        }
+        write!(f, " (location {})", self.location)
+    }
+}
+
+/// Everything active in a program, from [`Program::describe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramDescription {
+    pub attributes: Vec<ActiveVariable>,
+    pub uniforms: Vec<ActiveVariable>,
+}
+
+impl std::fmt::Display for ProgramDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Attributes:")?;
+        for attribute in &self.attributes {
+            writeln!(f, "  {attribute}")?;
+        }
+        writeln!(f, "Uniforms:")?;
+        for uniform in &self.uniforms {
+            writeln!(f, "  {uniform}")?;
+        }
+        Ok(())
+    }
+}
+
+type GetActive = unsafe fn(
+    GLuint,
+    GLuint,
+    GLsizei,
+    *mut GLsizei,
+    *mut GLint,
+    *mut GLenum,
+    *mut gl::types::GLchar,
+);
+
+impl Program {
+    pub fn active_attributes(&self, ctx: &GlContext) -> Vec<ActiveVariable> {
+        self.active_variables(
+            ctx,
+            gl::ACTIVE_ATTRIBUTES,
+            gl::ACTIVE_ATTRIBUTE_MAX_LENGTH,
+            gl::GetActiveAttrib,
+            Program::get_attrib_location,
+        )
+    }
+    pub fn active_uniforms(&self, ctx: &GlContext) -> Vec<ActiveVariable> {
+        self.active_variables(
+            ctx,
+            gl::ACTIVE_UNIFORMS,
+            gl::ACTIVE_UNIFORM_MAX_LENGTH,
+            gl::GetActiveUniform,
+            Program::get_uniform_location,
+        )
+    }
+    /// Every active attribute and uniform. Its `Display` lists them one per line.
+    pub fn describe(&self, ctx: &GlContext) -> ProgramDescription {
+        ProgramDescription {
+            attributes: self.active_attributes(ctx),
+            uniforms: self.active_uniforms(ctx),
+        }
+    }
+    fn active_variables(
+        &self,
+        ctx: &GlContext,
+        count: GLenum,
+        max_length: GLenum,
+        get_active: GetActive,
+        get_location: fn(&Program, &GlContext, &str) -> i32,
+    ) -> Vec<ActiveVariable> {
+        let id: GLuint = (*self).into();
+        let count = unsafe { self.get_iv(count) };
+        let max_length = unsafe { self.get_iv(max_length) }.max(1);
+        (0..count.max(0) as GLuint)
+            .map(|index| {
+                let mut name = vec![0u8; max_length as usize];
+                let (mut length, mut size, mut type_) = (0, 0, 0);
+                unsafe {
+                    get_active(
+                        id,
+                        index,
+                        max_length,
+                        &mut length,
+                        &mut size,
+                        &mut type_,
+                        name.as_mut_ptr() as _,
+                    )
+                };
+                name.truncate(length.max(0) as usize);
+                let name = String::from_utf8_lossy(&name).into_owned();
+                let location = get_location(self, ctx, &name);
+                ActiveVariable {
+                    name,
+                    type_: type_.into(),
+                    size,
+                    location,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock;
+
+    fn program() -> (GlContext, Program) {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| {
+            m.add_attribute("aPos", gl::FLOAT_VEC2, 0);
+            m.add_attribute("aColor", gl::FLOAT_VEC4, 1);
+            m.add_uniform("mvp", gl::FLOAT_MAT4, 1, 0);
+            m.add_uniform("lights[0]", gl::FLOAT_VEC3, 4, 4);
+            m.add_uniform("tex", gl::SAMPLER_2D, 1, 8);
+            m.add_uniform("mode", gl::UNSIGNED_INT, 1, 9);
+        });
+        (ctx, Program::from(1))
+    }
+
+    fn variable(name: &str, type_: GlType, size: GLint, location: GLint) -> ActiveVariable {
+        ActiveVariable {
+            name: name.into(),
+            type_,
+            size,
+            location,
+        }
+    }
+
+    #[test]
+    fn attributes() {
+        let (ctx, program) = program();
+        assert_eq!(
+            program.active_attributes(&ctx),
+            [
+                variable("aPos", GlType::FloatVec2, 1, 0),
+                variable("aColor", GlType::FloatVec4, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn uniforms() {
+        let (ctx, program) = program();
+        let uniforms = program.active_uniforms(&ctx);
+        assert_eq!(
+            uniforms,
+            [
+                variable("mvp", GlType::FloatMat4, 1, 0),
+                variable("lights[0]", GlType::FloatVec3, 4, 4),
+                variable("tex", GlType::Sampler2D, 1, 8),
+                variable("mode", GlType::Other(gl::UNSIGNED_INT), 1, 9),
+            ]
+        );
+        let names: Vec<_> = uniforms.iter().map(ActiveVariable::base_name).collect();
+        assert_eq!(names, ["mvp", "lights", "tex", "mode"]);
+    }
+
+    #[test]
+    fn describe() {
+        let (ctx, program) = program();
+        let description = program.describe(&ctx);
+        assert_eq!(description.attributes, program.active_attributes(&ctx));
+        assert_eq!(description.uniforms, program.active_uniforms(&ctx));
+        assert_eq!(
+            description.to_string(),
+            "\
+Attributes:
+  float2 aPos (location 0)
+  float4 aColor (location 1)
+Uniforms:
+  float4x4 mvp (location 0)
+  float3 lights[4] (location 4)
+  sampler2D tex (location 8)
+  <type 0x1405> mode (location 9)
+"
+        );
+    }
+
+    #[test]
+    fn no_variables() {
+        let ctx = mock::install();
+        mock::reset();
+        let description = Program::from(1).describe(&ctx);
+        assert!(description.attributes.is_empty() && description.uniforms.is_empty());
+    }
+}
@@ -1,32 +1,103 @@
+use std::{marker::PhantomData, ops::Deref};
+
 use derive_more::{From, Into};
 
+use crate::{
+    GlContext,
+    context::NotSend,
+    diagnostic::ShaderDiagnostic,
+    errors::{Errors, GlError, check_errors},
+    logging,
+};
+
+/// Which part of the pipeline a shader is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+impl ShaderStage {
+    pub fn gl_enum(self) -> gl::types::GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        }
+    }
+    /// `None` for anything but `GL_VERTEX_SHADER` and `GL_FRAGMENT_SHADER`.
+    pub fn from_gl_enum(typ: gl::types::GLenum) -> Option<ShaderStage> {
+        match typ {
+            gl::VERTEX_SHADER => Some(ShaderStage::Vertex),
+            gl::FRAGMENT_SHADER => Some(ShaderStage::Fragment),
+            _ => None,
+        }
+    }
+}
+
+impl From<ShaderStage> for gl::types::GLenum {
+    fn from(stage: ShaderStage) -> Self {
+        stage.gl_enum()
+    }
+}
+
+impl std::fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ShaderStage::Vertex => "vertex shader",
+                ShaderStage::Fragment => "fragment shader",
+            }
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ShaderError {
-    NoShader,
-    String(String),
+    NoShader(ShaderStage),
+    Compile(ShaderDiagnostic),
+    Binary(GxpError),
+}
+
+impl ShaderError {
+    /// The stage of the shader that failed, when known.
+    pub fn stage(&self) -> Option<ShaderStage> {
+        match self {
+            ShaderError::NoShader(stage) => Some(*stage),
+            ShaderError::Compile(d) => Some(d.stage),
+            ShaderError::Binary(GxpError::WrongStage { expected, .. }) => Some(*expected),
+            ShaderError::Binary(_) => None,
+        }
+    }
 }
 
 impl std::fmt::Display for ShaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ShaderError::NoShader => write!(f, "No Shader"),
-            ShaderError::String(s) => write!(f, "Shader did not compile:\n{}", s),
+            ShaderError::NoShader(stage) => write!(f, "No Shader ({stage})"),
+            ShaderError::Compile(d) => write!(f, "{d}"),
+            ShaderError::Binary(e) => write!(f, "Bad shader binary: {e}"),
         }
     }
 }
 
 impl std::error::Error for ShaderError {}
 
-///Make sure to call `.delete()` when you are done.
+///Make sure to call `.delete()` when you are done, or wrap it in an [`OwnedShader`].
 #[derive(From, Into, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Shader(gl::types::GLuint);
 
 impl Shader {
+    /// # Safety
+    /// `param` must be a valid `glGetShaderiv` parameter.
     pub unsafe fn get_iv(&self, param: gl::types::GLenum) -> i32 {
         let mut var = 0;
         unsafe { gl::GetShaderiv(self.0, param, &mut var) };
         var
     }
+    /// # Safety
+    /// The shader must not have been deleted.
     pub unsafe fn get_info_log(&self) -> String {
         let info_len = unsafe { self.get_iv(gl::INFO_LOG_LENGTH) };
         let mut info_log = vec![0u8; info_len as usize];
@@ -40,19 +111,60 @@ impl Shader {
         };
         unsafe { String::from_utf8_unchecked(info_log) }
     }
+    /// # Safety
+    /// The shader must not be used after this.
     pub unsafe fn delete(&self) {
         unsafe { gl::DeleteShader(self.0) }
     }
     pub fn is_null(&self) -> bool {
         self.0 == 0
     }
+    /// The stage GL reports for this shader, or `None` if it isn't a live shader.
+    pub fn stage(&self, _ctx: &GlContext) -> Option<ShaderStage> {
+        let typ = unsafe { self.get_iv(gl::SHADER_TYPE) };
+        ShaderStage::from_gl_enum(typ as _)
+    }
+    /// Compiles a vertex shader; see [`load_shader`].
+    pub fn vertex(ctx: &GlContext, source: &str) -> Result<Shader, ShaderError> {
+        load_shader(ctx, source, ShaderStage::Vertex)
+    }
+    /// Compiles a fragment shader; see [`load_shader`].
+    pub fn fragment(ctx: &GlContext, source: &str) -> Result<Shader, ShaderError> {
+        load_shader(ctx, source, ShaderStage::Fragment)
+    }
+}
+
+pub fn load_shader(
+    ctx: &GlContext,
+    source: &str,
+    stage: ShaderStage,
+) -> Result<Shader, ShaderError> {
+    load_shader_inner(ctx, source, stage, None)
+}
+
+/// Like [`load_shader`], but errors name `source_name` (e.g. the file the source came from).
+pub fn load_shader_named(
+    ctx: &GlContext,
+    source: &str,
+    stage: ShaderStage,
+    source_name: &str,
+) -> Result<Shader, ShaderError> {
+    load_shader_inner(ctx, source, stage, Some(source_name))
 }
 
-pub fn load_shader(source: &str, typ: gl::types::GLenum) -> Result<Shader, ShaderError> {
-    println!("Compiling Shader:\n{}", source);
-    let shader = unsafe { gl::CreateShader(typ) };
+fn load_shader_inner(
+    _ctx: &GlContext,
+    source: &str,
+    stage: ShaderStage,
+    source_name: Option<&str>,
+) -> Result<Shader, ShaderError> {
+    match source_name {
+        Some(name) => logging::trace!("Compiling {stage} {name}:\n{source}"),
+        None => logging::trace!("Compiling {stage}:\n{source}"),
+    }
+    let shader = unsafe { gl::CreateShader(stage.into()) };
     if shader == 0 {
-        return Err(ShaderError::NoShader);
+        return Err(ShaderError::NoShader(stage));
     }
     unsafe {
         let source_len = source.len() as i32;
@@ -63,8 +175,234 @@ pub fn load_shader(source: &str, typ: gl::types::GLenum) -> Result<Shader, Shade
         if compiled == 0 {
             let info_log = shader.get_info_log();
             shader.delete();
-            return Err(ShaderError::String(info_log));
+            return Err(ShaderError::Compile(ShaderDiagnostic::parse(
+                stage,
+                source_name,
+                source,
+                &info_log,
+            )));
         }
         Ok(shader)
     }
 }
+
+/// Why a GXP blob was refused by [`load_shader_binary`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GxpError {
+    /// Too short to hold a GXP header.
+    TooShort(usize),
+    BadMagic([u8; 4]),
+    UnsupportedVersion {
+        major: u8,
+        minor: u8,
+    },
+    /// The header says the program is longer than the blob.
+    Truncated {
+        header_size: u32,
+        len: usize,
+    },
+    /// A vertex program passed as a fragment shader, or the other way round.
+    WrongStage {
+        expected: ShaderStage,
+        found: ShaderStage,
+    },
+    /// vitaGL refused the binary, with every error `glShaderBinary` raised.
+    Rejected(Vec<GlError>),
+}
+
+impl std::fmt::Display for GxpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GxpError::TooShort(len) => write!(f, "{len} bytes is too short for a GXP header"),
+            GxpError::BadMagic(magic) => write!(f, "not a GXP program (magic {magic:02x?})"),
+            GxpError::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported GXP version {major}.{minor}")
+            }
+            GxpError::Truncated { header_size, len } => {
+                write!(
+                    f,
+                    "header says {header_size} bytes but only {len} were given"
+                )
+            }
+            GxpError::WrongStage { expected, found } => {
+                write!(f, "expected a {expected} program, found a {found} program")
+            }
+            GxpError::Rejected(errors) => {
+                let errors: Vec<String> = errors.iter().map(GlError::to_string).collect();
+                write!(f, "glShaderBinary failed with {}", errors.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GxpError {}
+
+const GXP_MAGIC: [u8; 4] = *b"GXP\0";
+const GXP_HEADER_LEN: usize = 0x18;
+const GXP_SIZE_OFFSET: usize = 0x08;
+const GXP_TYPE_OFFSET: usize = 0x14;
+/// vitaGL ignores the format, but GL wants one.
+const GXP_BINARY_FORMAT: gl::types::GLenum = 0;
+
+/// Checks the header of a compiled GXP program, as made by `psp2cgc` or dumped from
+/// `vglGetShaderBinary`, and that it is a program for `stage`.
+/// ```rust
+/// use vita_gl_helpers::shader::{GxpError, ShaderStage, check_gxp_header};
+/// let mut gxp = vec![0u8; 0x18];
+/// gxp[..6].copy_from_slice(b"GXP\0\x01\x04");
+/// gxp[0x08] = 0x18;
+/// gxp[0x14] = 1; // fragment
+/// assert_eq!(check_gxp_header(&gxp, ShaderStage::Fragment), Ok(()));
+/// assert!(matches!(
+///     check_gxp_header(&gxp, ShaderStage::Vertex),
+///     Err(GxpError::WrongStage { .. })
+/// ));
+/// ```
+pub fn check_gxp_header(bytes: &[u8], stage: ShaderStage) -> Result<(), GxpError> {
+    if bytes.len() < GXP_HEADER_LEN {
+        return Err(GxpError::TooShort(bytes.len()));
+    }
+    let magic = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    if magic != GXP_MAGIC {
+        return Err(GxpError::BadMagic(magic));
+    }
+    let (major, minor) = (bytes[4], bytes[5]);
+    if major != 1 || minor < 4 {
+        return Err(GxpError::UnsupportedVersion { major, minor });
+    }
+    let size = &bytes[GXP_SIZE_OFFSET..GXP_SIZE_OFFSET + 4];
+    let header_size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]);
+    if header_size as usize > bytes.len() {
+        return Err(GxpError::Truncated {
+            header_size,
+            len: bytes.len(),
+        });
+    }
+    let found = match bytes[GXP_TYPE_OFFSET] & 1 {
+        0 => ShaderStage::Vertex,
+        _ => ShaderStage::Fragment,
+    };
+    if found != stage {
+        return Err(GxpError::WrongStage {
+            expected: stage,
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Like [`load_shader`], but takes a precompiled GXP program instead of Cg source,
+/// skipping the runtime compiler. The header is checked with [`check_gxp_header`] first.
+///
+/// vitaGL reports a bad binary through `glGetError`. Errors already pending aren't this
+/// shader's, so they are [reported](crate::errors::report) against the caller first.
+#[track_caller]
+pub fn load_shader_binary(
+    ctx: &GlContext,
+    bytes: &[u8],
+    stage: ShaderStage,
+) -> Result<Shader, ShaderError> {
+    check_gxp_header(bytes, stage).map_err(ShaderError::Binary)?;
+    let caller = std::panic::Location::caller();
+    check_errors(
+        ctx,
+        "load_shader_binary",
+        || "errors pending before the call".to_string(),
+        caller.file(),
+        caller.line(),
+    );
+    let shader = unsafe { gl::CreateShader(stage.into()) };
+    if shader == 0 {
+        return Err(ShaderError::NoShader(stage));
+    }
+    unsafe {
+        gl::ShaderBinary(
+            1,
+            &shader,
+            GXP_BINARY_FORMAT,
+            bytes.as_ptr() as _,
+            bytes.len() as _,
+        );
+        let shader = Shader::from(shader);
+        let errors: Vec<GlError> = Errors::new(ctx).collect();
+        if errors.is_empty() {
+            Ok(shader)
+        } else {
+            shader.delete();
+            Err(ShaderError::Binary(GxpError::Rejected(errors)))
+        }
+    }
+}
+
+/// A [`Shader`] that is deleted when dropped. Derefs to the [`Shader`] handle.
+///
+/// `!Send`, so it is dropped on the GL thread.
+#[derive(PartialEq, Eq)]
+pub struct OwnedShader(Shader, NotSend);
+
+impl OwnedShader {
+    /// Takes ownership of `shader`, which will be deleted when this is dropped.
+    pub fn from_raw(_ctx: &GlContext, shader: Shader) -> Self {
+        OwnedShader(shader, PhantomData)
+    }
+    /// Gives up ownership without deleting the shader.
+    pub fn into_raw(self) -> Shader {
+        let shader = self.0;
+        std::mem::forget(self);
+        shader
+    }
+}
+
+impl Deref for OwnedShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        &self.0
+    }
+}
+
+impl Drop for OwnedShader {
+    fn drop(&mut self) {
+        unsafe { self.0.delete() }
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, GlCall};
+
+    fn gxp(stage: ShaderStage) -> Vec<u8> {
+        let mut gxp = vec![0u8; GXP_HEADER_LEN];
+        gxp[..6].copy_from_slice(b"GXP\0\x01\x04");
+        gxp[GXP_SIZE_OFFSET] = GXP_HEADER_LEN as u8;
+        gxp[GXP_TYPE_OFFSET] = (stage == ShaderStage::Fragment) as u8;
+        gxp
+    }
+
+    #[test]
+    fn binary_ignores_earlier_errors() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.errors.push_back(gl::INVALID_ENUM));
+        let shader = load_shader_binary(&ctx, &gxp(ShaderStage::Vertex), ShaderStage::Vertex);
+        assert!(shader.is_ok());
+        assert!(mock::with(|m| m.errors.is_empty()));
+    }
+
+    #[test]
+    fn binary_rejected_with_every_error() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.shader_binary_errors = vec![gl::INVALID_VALUE, gl::INVALID_OPERATION]);
+        let error = load_shader_binary(&ctx, &gxp(ShaderStage::Fragment), ShaderStage::Fragment)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            ShaderError::Binary(GxpError::Rejected(ref errors))
+                if *errors == [GlError::InvalidValue, GlError::InvalidOperation]
+        ));
+        assert_eq!(mock::take_calls().last(), Some(&GlCall::DeleteShader(1)));
+    }
+}
@@ -1,37 +1,55 @@
-use std::ffi::c_void;
+use std::{ffi::c_void, marker::PhantomData, ops::Deref};
 
 use derive_more::{From, Into};
 use gl::types::{GLenum, GLint, GLuint};
 
+use crate::{GlContext, context::NotSend, errors::check_wrapper};
+
 /// To create and delete textures:
-/// ```rust
-/// use vita_gl_helpers::texture::GenDelTexturesExt;
+/// ```rust,no_run
+/// use vita_gl_helpers::texture::{GenDelTexturesExt, Texture};
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
 /// let mut textures = vec![Texture::default();5];
-/// textures.gen_textures();
+/// textures.gen_textures(&ctx);
 /// // Do things with the textures
-/// textures.del_textures();
+/// textures.delete_textures(&ctx);
 /// ```
+/// Or use an [`OwnedTexture`] to have it deleted on drop.
 #[derive(From, Into, Clone, Copy, PartialEq, Eq, Default)]
 #[repr(C)]
 pub struct Texture(GLuint);
 
+/// A texture just bound to a bindpoint. Only made by binding, which took a [`GlContext`],
+/// so its methods don't ask for one again.
 #[non_exhaustive]
-pub struct BoundTexture(GLenum);
+pub struct BoundTexture(GLenum, NotSend);
 
 impl Texture {
-    pub fn bind(&self, bindpoint: GLenum) {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind(&self, _ctx: &GlContext, bindpoint: GLenum) {
         unsafe {
             gl::BindTexture(bindpoint, self.0);
         }
+        check_wrapper!("Texture::bind", bindpoint, self.0);
     }
-    pub fn bind_then<R>(&self, bindpoint: GLenum, then: impl FnOnce(BoundTexture) -> R) -> R {
-        self.bind(bindpoint);
-        then(BoundTexture(bindpoint))
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn bind_then<R>(
+        &self,
+        ctx: &GlContext,
+        bindpoint: GLenum,
+        then: impl FnOnce(BoundTexture) -> R,
+    ) -> R {
+        self.bind(ctx, bindpoint);
+        then(BoundTexture(bindpoint, PhantomData))
     }
 }
 
 impl BoundTexture {
-    pub fn image_2d(
+    /// # Safety
+    /// `pixels` is null, or points to `width` by `height` texels of `format` and `type_`, with rows padded to `GL_UNPACK_ALIGNMENT`.
+    #[allow(clippy::too_many_arguments)]
+    #[cfg_attr(gl_check, track_caller)]
+    pub unsafe fn image_2d(
         &self,
         level: impl Into<GLint>,
         internalformat: impl Into<GLint>,
@@ -41,45 +59,109 @@ impl BoundTexture {
         type_: impl Into<GLenum>,
         pixels: *const c_void,
     ) {
+        let (level, internalformat) = (level.into(), internalformat.into());
+        let (width, height) = (width.into(), height.into());
+        let (format, type_) = (format.into(), type_.into());
         unsafe {
             gl::TexImage2D(
                 self.0,
-                level.into(),
-                internalformat.into(),
-                width.into(),
-                height.into(),
+                level,
+                internalformat,
+                width,
+                height,
                 0,
-                format.into(),
-                type_.into(),
+                format,
+                type_,
                 pixels,
             );
         }
+        check_wrapper!(
+            "BoundTexture::image_2d",
+            self.0,
+            level,
+            internalformat,
+            width,
+            height,
+            format,
+            type_,
+            pixels
+        );
     }
+    #[cfg_attr(gl_check, track_caller)]
     pub fn gen_mipmap(&self) {
         unsafe {
             gl::GenerateMipmap(self.0);
         }
+        check_wrapper!("BoundTexture::gen_mipmap", self.0);
     }
+    #[cfg_attr(gl_check, track_caller)]
     pub fn parameter_i(&self, pname: impl Into<GLenum>, param: impl Into<GLint>) {
+        let (pname, param) = (pname.into(), param.into());
         unsafe {
-            gl::TexParameteri(self.0, pname.into(), param.into());
+            gl::TexParameteri(self.0, pname, param);
         }
+        check_wrapper!("BoundTexture::parameter_i", self.0, pname, param);
     }
 }
 
 pub trait GenDelTexturesExt {
-    fn gen_textures(&mut self);
-    fn delete_textures(&mut self);
+    fn gen_textures(&mut self, ctx: &GlContext);
+    fn delete_textures(&mut self, ctx: &GlContext);
 }
 
 impl<T: AsMut<[Texture]>> GenDelTexturesExt for T {
-    fn gen_textures(&mut self) {
+    #[cfg_attr(gl_check, track_caller)]
+    fn gen_textures(&mut self, _ctx: &GlContext) {
         let as_mut = self.as_mut();
         unsafe { gl::GenTextures(as_mut.len() as i32, as_mut.as_mut_ptr() as _) }
+        check_wrapper!("gen_textures", as_mut.len());
     }
 
-    fn delete_textures(&mut self) {
+    #[cfg_attr(gl_check, track_caller)]
+    fn delete_textures(&mut self, _ctx: &GlContext) {
         let as_mut = self.as_mut();
         unsafe { gl::DeleteTextures(as_mut.len() as i32, as_mut.as_mut_ptr() as _) }
+        check_wrapper!("delete_textures", as_mut.len());
+    }
+}
+
+/// A [`Texture`] that is deleted when dropped. Derefs to the [`Texture`] handle.
+///
+/// `!Send`, so it is dropped on the GL thread.
+#[derive(PartialEq, Eq)]
+pub struct OwnedTexture(Texture, NotSend);
+
+impl OwnedTexture {
+    #[cfg_attr(gl_check, track_caller)]
+    pub fn generate(ctx: &GlContext) -> Self {
+        let mut texture = [Texture::default()];
+        texture.gen_textures(ctx);
+        OwnedTexture(texture[0], PhantomData)
+    }
+    /// Takes ownership of `texture`, which will be deleted when this is dropped.
+    pub fn from_raw(_ctx: &GlContext, texture: Texture) -> Self {
+        OwnedTexture(texture, PhantomData)
+    }
+    /// Gives up ownership without deleting the texture.
+    pub fn into_raw(self) -> Texture {
+        let texture = self.0;
+        std::mem::forget(self);
+        texture
+    }
+}
+
+impl Deref for OwnedTexture {
+    type Target = Texture;
+
+    fn deref(&self) -> &Texture {
+        &self.0
+    }
+}
+
+impl Drop for OwnedTexture {
+    fn drop(&mut self) {
+        // Made with a context, and can't have left its thread since.
+        let ctx = unsafe { GlContext::assume_current() };
+        [self.0].delete_textures(&ctx);
     }
 }
@@ -1,36 +1,94 @@
 use derive_more::From;
 
-use crate::program::Program;
+use crate::{
+    GlContext,
+    errors::check_wrapper,
+    program::{Program, TableLookup},
+    reflect::{ActiveVariable, GlType},
+};
+
+#[cfg(feature = "derive")]
+pub use vita_gl_helpers_derive::UniformTable;
+
+/// A uniform setter, made from the location it sets.
+///
+/// Setters on a missing uniform (location -1) do nothing.
+pub trait Uniform: Sized {
+    /// The shader types this setter can upload to.
+    const TYPES: &'static [GlType];
+    fn from_location(location: gl::types::GLint) -> Self;
+    fn location(&self) -> gl::types::GLint;
+    fn is_missing(&self) -> bool {
+        self.location() < 0
+    }
+}
 
 macro_rules! uniform_def {
-    ($name:ident,$accept:ty) => {
+    ($name:ident,$accept:ty,[$($types:ident),*]) => {
         #[derive(Default, Clone, Copy, PartialEq, Eq)]
         pub struct $name(pub gl::types::GLint);
+        impl Uniform for $name {
+            const TYPES: &'static [GlType] = &[$(GlType::$types),*];
+            fn from_location(location: gl::types::GLint) -> Self {
+                $name(location)
+            }
+            fn location(&self) -> gl::types::GLint {
+                self.0
+            }
+        }
         impl $name {
-            pub fn set(&self, to: $accept) {
-                self.set_multi(&[to])
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set(&self, ctx: &GlContext, to: $accept) {
+                self.set_multi(ctx, &[to])
             }
-            pub fn set_multi(&self, to: &[$accept]) {
-                self.set_subrange(0, to)
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set_multi(&self, ctx: &GlContext, to: &[$accept]) {
+                self.set_subrange(ctx, 0, to)
             }
-            pub fn set_subrange(&self, offset: usize, to: &[$accept]) {
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set_subrange(&self, _ctx: &GlContext, offset: usize, to: &[$accept]) {
+                if self.is_missing() {
+                    return;
+                }
                 unsafe {
                     gl::$name(self.0 + offset as i32, to.len() as _, to.as_ptr() as _);
                 }
+                check_wrapper!(concat!(stringify!($name), "::set"), self.0, offset, to);
             }
         }
     };
-    ($name:ident,$accept:ty,mat) => {
+    ($name:ident,$accept:ty,[$($types:ident),*],mat) => {
         #[derive(Default, Clone, Copy, PartialEq, Eq)]
         pub struct $name(pub gl::types::GLint);
+        impl Uniform for $name {
+            const TYPES: &'static [GlType] = &[$(GlType::$types),*];
+            fn from_location(location: gl::types::GLint) -> Self {
+                $name(location)
+            }
+            fn location(&self) -> gl::types::GLint {
+                self.0
+            }
+        }
         impl $name {
-            pub fn set(&self, to: $accept, transpose: bool) {
-                self.set_multi(&[to], transpose)
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set(&self, ctx: &GlContext, to: $accept, transpose: bool) {
+                self.set_multi(ctx, &[to], transpose)
             }
-            pub fn set_multi(&self, to: &[$accept], transpose: bool) {
-                self.set_subrange(0, to, transpose)
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set_multi(&self, ctx: &GlContext, to: &[$accept], transpose: bool) {
+                self.set_subrange(ctx, 0, to, transpose)
             }
-            pub fn set_subrange(&self, offset: usize, to: &[$accept], transpose: bool) {
+            #[cfg_attr(gl_check, track_caller)]
+            pub fn set_subrange(
+                &self,
+                _ctx: &GlContext,
+                offset: usize,
+                to: &[$accept],
+                transpose: bool,
+            ) {
+                if self.is_missing() {
+                    return;
+                }
                 unsafe {
                     gl::$name(
                         self.0 + offset as i32,
@@ -39,24 +97,31 @@ macro_rules! uniform_def {
                         to.as_ptr() as _,
                     );
                 }
+                check_wrapper!(
+                    concat!(stringify!($name), "::set"),
+                    self.0,
+                    offset,
+                    to,
+                    transpose
+                );
             }
         }
     };
 }
 
-uniform_def!(Uniform1fv, f32);
-uniform_def!(Uniform2fv, [f32; 2]);
-uniform_def!(Uniform3fv, [f32; 3]);
-uniform_def!(Uniform4fv, [f32; 4]);
+uniform_def!(Uniform1fv, f32, [Float, Bool]);
+uniform_def!(Uniform2fv, [f32; 2], [FloatVec2, BoolVec2]);
+uniform_def!(Uniform3fv, [f32; 3], [FloatVec3, BoolVec3]);
+uniform_def!(Uniform4fv, [f32; 4], [FloatVec4, BoolVec4]);
 
-uniform_def!(Uniform1iv, i32);
-uniform_def!(Uniform2iv, [i32; 2]);
-uniform_def!(Uniform3iv, [i32; 3]);
-uniform_def!(Uniform4iv, [i32; 4]);
+uniform_def!(Uniform1iv, i32, [Int, Bool, Sampler2D, SamplerCube]);
+uniform_def!(Uniform2iv, [i32; 2], [IntVec2, BoolVec2]);
+uniform_def!(Uniform3iv, [i32; 3], [IntVec3, BoolVec3]);
+uniform_def!(Uniform4iv, [i32; 4], [IntVec4, BoolVec4]);
 
-uniform_def!(UniformMatrix2fv, [f32; 4], mat);
-uniform_def!(UniformMatrix3fv, [f32; 9], mat);
-uniform_def!(UniformMatrix4fv, [f32; 16], mat);
+uniform_def!(UniformMatrix2fv, [f32; 4], [FloatMat2], mat);
+uniform_def!(UniformMatrix3fv, [f32; 9], [FloatMat3], mat);
+uniform_def!(UniformMatrix4fv, [f32; 16], [FloatMat4], mat);
 // uniform_def!(UniformMatrix2x3fv, [f32; 6], mat); Sadly, we don't have these :(
 // uniform_def!(UniformMatrix3x2fv, [f32; 6], mat);
 // uniform_def!(UniformMatrix2x4fv, [f32; 8], mat);
@@ -75,30 +140,240 @@ impl std::fmt::Display for MissingUniforms {
 
 impl std::error::Error for MissingUniforms {}
 
+/// What a [`UniformTable`] field expects of its uniform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UniformField {
+    pub name: &'static str,
+    pub types: &'static [GlType],
+    /// The array length, if the table states one.
+    pub size: Option<gl::types::GLint>,
+    pub optional: bool,
+}
+
+/// A uniform whose type or array length doesn't match its table field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformMismatch {
+    pub field: UniformField,
+    pub found: GlType,
+    pub found_size: gl::types::GLint,
+}
+
+impl std::fmt::Display for UniformMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected: Vec<String> = self.field.types.iter().map(GlType::to_string).collect();
+        write!(f, "{}: expected {}", self.field.name, expected.join(" or "))?;
+        if let Some(size) = self.field.size {
+            write!(f, " with length {size}")?;
+        }
+        write!(f, ", found {}", self.found)?;
+        if self.field.size.is_some() || self.found_size != 1 {
+            write!(f, " with length {}", self.found_size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything wrong with a [`UniformTable`], from [`UniformTable::with_locations_checked`].
+#[derive(Debug)]
+pub struct UniformTableError {
+    pub missing: Vec<&'static str>,
+    pub mismatched: Vec<UniformMismatch>,
+}
+
+impl std::fmt::Display for UniformTableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.missing.is_empty() {
+            write!(f, "Missing Uniforms: [{}]", self.missing.join(","))?;
+            if !self.mismatched.is_empty() {
+                write!(f, ", ")?;
+            }
+        }
+        if !self.mismatched.is_empty() {
+            let mismatched: Vec<String> = self.mismatched.iter().map(|m| m.to_string()).collect();
+            write!(f, "Mismatched Uniforms: [{}]", mismatched.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UniformTableError {}
+
+/// A set of uniforms looked up together. Usually derived:
+/// ```rust
+/// use vita_gl_helpers::uniforms::{Uniform2fv, Uniform3fv, UniformMatrix4fv, UniformTable};
+/// #[derive(UniformTable)]
+/// pub struct MyUniforms {
+///     /// Size of each rectangle.
+///     pub rect_dim: Uniform2fv,
+///     #[gl(name = "uProjection", optional)]
+///     pub projection: UniformMatrix4fv,
+///     /// Checked to be a 4 element array by `with_locations_checked`.
+///     #[gl(len = 4)]
+///     pub lights: Uniform3fv,
+/// }
+/// ```
+/// or made with [`uniform_table!`](crate::uniform_table).
+///
+/// Fields marked `#[gl(optional)]` (`#[optional]` in the macro) are left at location -1
+/// when the shader doesn't have them, e.g. because the compiler stripped them as unused.
 pub trait UniformTable: Sized {
-    fn with_locations_from(p: &Program) -> Result<Self, MissingUniforms>;
+    /// Looks up every uniform without failing.
+    fn lookup(ctx: &GlContext, p: &Program) -> TableLookup<Self>;
+    /// Fails only if a required uniform is missing.
+    fn with_locations_from(ctx: &GlContext, p: &Program) -> Result<Self, MissingUniforms> {
+        Self::lookup(ctx, p).required()
+    }
+    /// Fails if any uniform is missing, optional or not.
+    fn with_locations_from_strict(ctx: &GlContext, p: &Program) -> Result<Self, MissingUniforms> {
+        Self::lookup(ctx, p).strict()
+    }
+    fn fields() -> Vec<UniformField>;
+    /// Like [`with_locations_from`](UniformTable::with_locations_from), but also checks each
+    /// field's type and array length against the program's active uniforms.
+    fn with_locations_checked(ctx: &GlContext, p: &Program) -> Result<Self, UniformTableError> {
+        let lookup = Self::lookup(ctx, p);
+        let mismatched = mismatched_fields(Self::fields(), &p.active_uniforms(ctx));
+        if lookup.missing_required.is_empty() && mismatched.is_empty() {
+            Ok(lookup.table)
+        } else {
+            Err(UniformTableError {
+                missing: lookup.missing_required,
+                mismatched,
+            })
+        }
+    }
+}
+
+/// The fields whose uniform in `active` has the wrong type or length.
+pub(crate) fn mismatched_fields(
+    fields: Vec<UniformField>,
+    active: &[ActiveVariable],
+) -> Vec<UniformMismatch> {
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let uniform = active
+                .iter()
+                .find(|u| u.base_name() == field_base_name(&field))?;
+            let type_ok = field.types.contains(&uniform.type_);
+            let size_ok = field.size.is_none_or(|size| size == uniform.size);
+            (!type_ok || !size_ok).then_some(UniformMismatch {
+                field,
+                found: uniform.type_,
+                found_size: uniform.size,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn field_base_name(field: &UniformField) -> &'static str {
+    field.name.strip_suffix("[0]").unwrap_or(field.name)
 }
 
 #[macro_export]
 macro_rules! uniform_table {
-    ($sname:ident,$($lname:ident : $t:ident => $lstr:expr),*) => {
+    ($sname:ident,$($(#[$opt:ident])? $lname:ident : $t:ident => $lstr:expr),*) => {
         #[derive(Default,Clone,Copy,PartialEq,Eq)]
         pub struct $sname {
             $($lname: $crate::uniforms::$t),*
         }
         impl $crate::uniforms::UniformTable for $sname {
-            fn with_locations_from(p: &$crate::program::Program) -> Result<Self, $crate::uniforms::MissingUniforms> {
-                let to_check = [$($lstr),*];
-                let locations = [$(p.get_uniform_location($lstr)),*];
-                let errors: Vec<&'static str> = to_check.into_iter().zip(locations.iter()).filter_map(|(n,&l)| if l == -1 {Some(n)} else {None}).collect();
-                if !errors.is_empty() {
-                    return Err($crate::uniforms::MissingUniforms(errors));
-                }
-                let mut locations_iter = locations.into_iter();
-                Ok($sname {
-                    $($lname: $crate::uniforms::$t(locations_iter.next().unwrap())),*
+            fn lookup(
+                ctx: &$crate::GlContext,
+                p: &$crate::program::Program,
+            ) -> $crate::program::TableLookup<Self> {
+                $crate::program::lookup_locations(
+                    [$(($lstr, $crate::__table_entry_optional!($($opt)?))),*],
+                    |n| p.get_uniform_location(ctx, n),
+                ).map(|locations| {
+                    let mut locations_iter = locations.into_iter();
+                    $sname {
+                        $($lname: $crate::uniforms::$t(locations_iter.next().unwrap())),*
+                    }
                 })
             }
+            fn fields() -> Vec<$crate::uniforms::UniformField> {
+                vec![$($crate::uniforms::UniformField {
+                    name: $lstr,
+                    types: <$crate::uniforms::$t as $crate::uniforms::Uniform>::TYPES,
+                    size: None,
+                    optional: $crate::__table_entry_optional!($($opt)?),
+                }),*]
+            }
         }
     };
 }
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::mock::{self, GlCall};
+
+    uniform_table!(Lights, rect_dim: Uniform2fv => "rect_dim", #[optional] lights: Uniform3fv => "lights");
+
+    #[test]
+    fn setters_offset_location() {
+        let ctx = mock::install();
+        mock::reset();
+        Uniform3fv(4).set_subrange(&ctx, 2, &[[1.0, 2.0, 3.0]]);
+        UniformMatrix2fv(1).set(&ctx, [1.0, 0.0, 0.0, 1.0], true);
+        Uniform1iv(-1).set(&ctx, 3);
+        assert_eq!(
+            mock::take_calls(),
+            [
+                GlCall::Uniformfv {
+                    components: 3,
+                    location: 6,
+                    count: 1,
+                    value: vec![1.0, 2.0, 3.0],
+                },
+                GlCall::UniformMatrixfv {
+                    dim: 2,
+                    location: 1,
+                    count: 1,
+                    transpose: true,
+                    value: vec![1.0, 0.0, 0.0, 1.0],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn table_lookup() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.add_uniform("rect_dim", gl::FLOAT_VEC2, 1, 3));
+        let table = Lights::with_locations_from(&ctx, &Program::from(1))
+            .ok()
+            .unwrap();
+        assert_eq!(table.rect_dim.location(), 3);
+        assert!(table.lights.is_missing());
+        let error = Lights::with_locations_from_strict(&ctx, &Program::from(1))
+            .err()
+            .unwrap();
+        assert_eq!(error.0, ["lights"]);
+        mock::reset();
+        let error = Lights::with_locations_from(&ctx, &Program::from(1))
+            .err()
+            .unwrap();
+        assert_eq!(error.0, ["rect_dim"]);
+    }
+
+    #[test]
+    fn checked_table_reports_mismatches() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| {
+            m.add_uniform("rect_dim", gl::FLOAT_VEC3, 1, 0);
+            m.add_uniform("lights[0]", gl::FLOAT_VEC3, 4, 1);
+        });
+        let error = Lights::with_locations_checked(&ctx, &Program::from(1))
+            .err()
+            .unwrap();
+        assert!(error.missing.is_empty());
+        assert_eq!(
+            error.to_string(),
+            "Mismatched Uniforms: [rect_dim: expected float2 or bool2, found float3]"
+        );
+    }
+}
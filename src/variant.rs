@@ -0,0 +1,376 @@
+use std::collections::{BTreeMap, HashMap};
+
+use derive_more::From;
+
+use crate::{
+    GlContext,
+    attribute::{AttributeTable, MissingAttributes},
+    preprocess::{EmbeddedResolver, IncludeResolver, PreprocessError, ShaderSourceBuilder},
+    program::{OwnedProgram, ProgramError, link_program},
+    shader::{OwnedShader, Shader, ShaderError, ShaderStage},
+    uniforms::{MissingUniforms, UniformTable},
+};
+
+/// The value of one define in a [`VariantKey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Define {
+    /// `#define NAME 1` when set. Left undefined otherwise, so `#ifdef` works as well as `#if`.
+    Flag(bool),
+    /// `#define NAME value`
+    Int(i64),
+}
+
+/// The defines that pick out one variant of a shader.
+///
+/// Two keys with the same defines are equal no matter what order they were set in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct VariantKey(BTreeMap<String, Define>);
+
+impl VariantKey {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn flag(mut self, name: impl Into<String>, on: bool) -> Self {
+        self.0.insert(name.into(), Define::Flag(on));
+        self
+    }
+    pub fn int(mut self, name: impl Into<String>, value: i64) -> Self {
+        self.0.insert(name.into(), Define::Int(value));
+        self
+    }
+    pub fn get(&self, name: &str) -> Option<Define> {
+        self.0.get(name).copied()
+    }
+    pub fn defines(&self) -> impl Iterator<Item = (&str, Define)> {
+        self.0.iter().map(|(name, define)| (name.as_str(), *define))
+    }
+    fn apply<R: IncludeResolver>(
+        &self,
+        mut builder: ShaderSourceBuilder<R>,
+    ) -> ShaderSourceBuilder<R> {
+        for (name, define) in self.defines() {
+            builder = match define {
+                Define::Flag(false) => builder,
+                Define::Flag(true) => builder.define_flag(name),
+                Define::Int(value) => builder.define(name, value.to_string()),
+            };
+        }
+        builder
+    }
+}
+
+impl std::fmt::Display for VariantKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (i, (name, define)) in self.defines().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match define {
+                Define::Flag(on) => write!(f, "{name}={}", on as u8)?,
+                Define::Int(value) => write!(f, "{name}={value}")?,
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+#[derive(Debug, From)]
+pub enum VariantError {
+    Preprocess(PreprocessError),
+    Shader(ShaderError),
+    Program(ProgramError),
+    MissingUniforms(MissingUniforms),
+    MissingAttributes(MissingAttributes),
+}
+
+impl std::fmt::Display for VariantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariantError::Preprocess(e) => write!(f, "{e}"),
+            VariantError::Shader(e) => write!(f, "{e}"),
+            VariantError::Program(e) => write!(f, "{e}"),
+            VariantError::MissingUniforms(e) => write!(f, "{e}"),
+            VariantError::MissingAttributes(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for VariantError {}
+
+/// One compiled and linked variant, with its tables looked up.
+pub struct Variant<U, A> {
+    pub program: OwnedProgram,
+    pub uniforms: U,
+    pub attributes: A,
+}
+
+impl<U, A> Variant<U, A> {
+    pub fn use_me(&self, ctx: &GlContext) {
+        self.program.use_me(ctx)
+    }
+}
+
+impl<U: UniformTable, A: AttributeTable> Variant<U, A> {
+    /// Compiles each stage with `compile`, links them and looks up the tables.
+    /// The shaders are deleted once linked, or on failure.
+    pub(crate) fn build<E>(
+        ctx: &GlContext,
+        mut compile: impl FnMut(ShaderStage) -> Result<Shader, E>,
+    ) -> Result<Self, E>
+    where
+        E: From<ProgramError> + From<MissingUniforms> + From<MissingAttributes>,
+    {
+        let vertex = OwnedShader::from_raw(ctx, compile(ShaderStage::Vertex)?);
+        let fragment = OwnedShader::from_raw(ctx, compile(ShaderStage::Fragment)?);
+        let program = OwnedProgram::from_raw(ctx, link_program(ctx, *vertex, *fragment)?);
+        Ok(Variant {
+            uniforms: U::with_locations_from(ctx, &program)?,
+            attributes: A::with_locations_from(ctx, &program)?,
+            program,
+        })
+    }
+}
+
+/// Every variant of a vertex/fragment shader pair, compiled and linked the first time it is asked for.
+///
+/// Defines are prepended to both sources, which are otherwise run through the
+/// [preprocessor](crate::preprocess), so they can `#include` from the resolver.
+/// ```rust,no_run
+/// use vita_gl_helpers::{
+///     attribute::{Attribute, AttributeTable},
+///     uniforms::{Uniform4fv, UniformTable},
+///     variant::{ShaderVariants, VariantKey},
+/// };
+/// #[derive(AttributeTable)]
+/// struct Attributes {
+///     #[gl(name = "aPos")]
+///     pos: Attribute,
+///     #[gl(name = "aTexCoord", optional)]
+///     tex_coord: Attribute,
+/// }
+/// #[derive(UniformTable)]
+/// struct Uniforms {
+///     #[gl(optional)]
+///     fog_color: Uniform4fv,
+/// }
+/// # let (vertex_source, fragment_source) = ("", "");
+/// let ctx = vita_gl_helpers::initialise_default().unwrap();
+/// let mut variants = ShaderVariants::<Uniforms, Attributes>::from_sources(vertex_source, fragment_source);
+/// let foggy = VariantKey::new().flag("FOG", true).int("LIGHTS", 2);
+/// let variant = variants.get(&ctx, &foggy).unwrap();
+/// variant.use_me(&ctx);
+/// variant.uniforms.fog_color.set(&ctx, [0.5, 0.5, 0.6, 1.0]);
+/// ```
+pub struct ShaderVariants<U, A, R: IncludeResolver = EmbeddedResolver> {
+    resolver: R,
+    vertex: String,
+    fragment: String,
+    variants: HashMap<VariantKey, Variant<U, A>>,
+}
+
+impl<U: UniformTable, A: AttributeTable> ShaderVariants<U, A> {
+    /// Variants of two sources that don't `#include` anything.
+    pub fn from_sources(vertex_source: &str, fragment_source: &str) -> Self {
+        Self::with_resolver(
+            EmbeddedResolver::new([("vertex", vertex_source), ("fragment", fragment_source)]),
+            "vertex",
+            "fragment",
+        )
+    }
+}
+
+impl<U: UniformTable, A: AttributeTable, R: IncludeResolver> ShaderVariants<U, A, R> {
+    /// Variants of the sources `resolver` gives for `vertex` and `fragment`.
+    pub fn with_resolver(resolver: R, vertex: &str, fragment: &str) -> Self {
+        ShaderVariants {
+            resolver,
+            vertex: vertex.to_string(),
+            fragment: fragment.to_string(),
+            variants: HashMap::new(),
+        }
+    }
+    /// The variant for `key`, compiling and linking it if this is the first time it's needed.
+    ///
+    /// Failures aren't cached, so asking again retries.
+    pub fn get(
+        &mut self,
+        ctx: &GlContext,
+        key: &VariantKey,
+    ) -> Result<&Variant<U, A>, VariantError> {
+        if !self.variants.contains_key(key) {
+            let variant = self.build(ctx, key)?;
+            self.variants.insert(key.clone(), variant);
+        }
+        Ok(&self.variants[key])
+    }
+    /// The variant for `key` if it has already been built.
+    pub fn get_built(&self, key: &VariantKey) -> Option<&Variant<U, A>> {
+        self.variants.get(key)
+    }
+    /// Builds each of `keys` up front, e.g. behind a loading screen.
+    pub fn prebuild<'a>(
+        &mut self,
+        ctx: &GlContext,
+        keys: impl IntoIterator<Item = &'a VariantKey>,
+    ) -> Result<(), VariantError> {
+        for key in keys {
+            self.get(ctx, key)?;
+        }
+        Ok(())
+    }
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&VariantKey, &Variant<U, A>)> {
+        self.variants.iter()
+    }
+    /// Deletes every built variant.
+    pub fn clear(&mut self) {
+        self.variants.clear();
+    }
+    fn build(&self, ctx: &GlContext, key: &VariantKey) -> Result<Variant<U, A>, VariantError> {
+        let builder = key.apply(ShaderSourceBuilder::new(&self.resolver));
+        Variant::build(ctx, |stage| {
+            let name = match stage {
+                ShaderStage::Vertex => &self.vertex,
+                ShaderStage::Fragment => &self.fragment,
+            };
+            Ok(builder.build(name)?.compile(ctx, stage)?)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "host"))]
+mod tests {
+    use super::*;
+    use crate::{
+        attribute_table,
+        mock::{self, GlCall},
+        uniform_table,
+    };
+
+    uniform_table!(Uniforms, #[optional] fog_color: Uniform4fv => "fog_color");
+    attribute_table!(Attributes, #[optional] pos => "aPos");
+
+    fn variants() -> ShaderVariants<Uniforms, Attributes> {
+        ShaderVariants::from_sources("vertex body", "fragment body")
+    }
+
+    fn sources(calls: &[GlCall]) -> Vec<&str> {
+        calls
+            .iter()
+            .filter_map(|call| match call {
+                GlCall::ShaderSource { source, .. } => Some(source.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn programs_created(calls: &[GlCall]) -> usize {
+        calls
+            .iter()
+            .filter(|call| matches!(call, GlCall::CreateProgram(_)))
+            .count()
+    }
+
+    #[test]
+    fn built_once_then_cached() {
+        let ctx = mock::install();
+        mock::reset();
+        let mut variants = variants();
+        let key = VariantKey::new().flag("FOG", true).int("LIGHTS", 2);
+        assert!(variants.get_built(&key).is_none());
+        let first = u32::from(*variants.get(&ctx, &key).ok().unwrap().program);
+        let calls = mock::take_calls();
+        assert_eq!(programs_created(&calls), 1);
+        let sources = sources(&calls);
+        assert_eq!(sources.len(), 2);
+        assert!(sources.iter().all(|source| source.contains("#define FOG")
+            && source.contains("#define LIGHTS 2")));
+
+        let again = u32::from(*variants.get(&ctx, &key).ok().unwrap().program);
+        assert_eq!(again, first);
+        assert!(mock::take_calls().is_empty());
+        assert_eq!(variants.len(), 1);
+        assert!(variants.get_built(&key).is_some());
+    }
+
+    #[test]
+    fn define_order_does_not_matter() {
+        let ctx = mock::install();
+        mock::reset();
+        let a = VariantKey::new().flag("FOG", true).int("LIGHTS", 2);
+        let b = VariantKey::new().int("LIGHTS", 2).flag("FOG", true);
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), b.to_string());
+        let mut variants = variants();
+        variants.get(&ctx, &a).ok().unwrap();
+        variants.get(&ctx, &b).ok().unwrap();
+        assert_eq!(programs_created(&mock::take_calls()), 1);
+        assert_ne!(a, a.clone().int("LIGHTS", 3));
+    }
+
+    #[test]
+    fn unset_flag_is_undefined() {
+        let ctx = mock::install();
+        mock::reset();
+        let key = VariantKey::new().flag("FOG", false);
+        assert_ne!(key, VariantKey::new());
+        variants().get(&ctx, &key).ok().unwrap();
+        let calls = mock::take_calls();
+        let sources = sources(&calls);
+        assert_eq!(sources.len(), 2);
+        assert!(sources.iter().all(|source| !source.contains("FOG")));
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let ctx = mock::install();
+        mock::reset();
+        mock::with(|m| m.link_status = false);
+        let mut variants = variants();
+        let key = VariantKey::new().flag("FOG", true);
+        let error = variants.get(&ctx, &key).err().unwrap();
+        assert!(matches!(
+            error,
+            VariantError::Program(ProgramError::Link(_))
+        ));
+        assert!(variants.is_empty());
+
+        mock::with(|m| m.link_status = true);
+        mock::take_calls();
+        assert!(variants.get(&ctx, &key).is_ok());
+        assert_eq!(programs_created(&mock::take_calls()), 1);
+        assert_eq!(variants.len(), 1);
+    }
+
+    #[test]
+    fn clear_deletes_programs() {
+        let ctx = mock::install();
+        mock::reset();
+        let mut variants = variants();
+        let keys = [VariantKey::new(), VariantKey::new().flag("FOG", true)];
+        variants.prebuild(&ctx, &keys).ok().unwrap();
+        let mut programs: Vec<u32> = variants
+            .iter()
+            .map(|(_, variant)| u32::from(*variant.program))
+            .collect();
+        mock::take_calls();
+        variants.clear();
+        assert!(variants.is_empty());
+        let mut deleted: Vec<u32> = mock::take_calls()
+            .into_iter()
+            .filter_map(|call| match call {
+                GlCall::DeleteProgram(program) => Some(program),
+                _ => None,
+            })
+            .collect();
+        programs.sort();
+        deleted.sort();
+        assert_eq!(deleted, programs);
+    }
+}
@@ -0,0 +1,121 @@
+use bytemuck::Pod;
+
+use crate::attribute::{AttributeFormat, AttributeSize, AttributeType};
+
+#[cfg(feature = "derive")]
+pub use vita_gl_helpers_derive::Vertex;
+
+/// One field of an interleaved [`Vertex`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexField {
+    /// The shader attribute this field feeds.
+    pub name: &'static str,
+    pub format: AttributeFormat,
+    /// Byte offset of the field within the vertex.
+    pub offset: usize,
+}
+
+/// An interleaved vertex layout. Usually derived:
+/// ```rust
+/// use bytemuck::{Pod, Zeroable};
+/// use vita_gl_helpers::vertex::Vertex;
+/// #[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+/// #[repr(C)]
+/// struct MyVertex {
+///     #[gl(name = "aPos")]
+///     pos: [f32; 2],
+///     #[gl(name = "aColor", normalized)]
+///     color: [u8; 4],
+/// }
+/// assert_eq!(MyVertex::FIELDS[1].offset, 8);
+/// ```
+/// Then bind a whole buffer of them with [`Buffer::bind_vertices`](crate::buffer::Buffer::bind_vertices).
+///
+/// The derive needs `C` among the struct's `repr` options, so field offsets are fixed:
+/// ```rust,compile_fail
+/// # use bytemuck::{Pod, Zeroable};
+/// # use vita_gl_helpers::vertex::Vertex;
+/// #[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+/// #[repr(transparent)]
+/// struct Position {
+///     pos: [f32; 2],
+/// }
+/// ```
+pub trait Vertex: Pod {
+    const FIELDS: &'static [VertexField];
+}
+
+/// A field type that can be read as a vertex attribute.
+pub trait VertexAttribute {
+    const FORMAT: AttributeFormat;
+}
+
+macro_rules! vertex_attribute {
+    ($t:ty, $type_:ident) => {
+        vertex_attribute!($t, $type_, ONE);
+        vertex_attribute!([$t; 1], $type_, ONE);
+        vertex_attribute!([$t; 2], $type_, TWO);
+        vertex_attribute!([$t; 3], $type_, THREE);
+        vertex_attribute!([$t; 4], $type_, FOUR);
+    };
+    ($t:ty, $type_:ident, $size:ident) => {
+        impl VertexAttribute for $t {
+            const FORMAT: AttributeFormat = AttributeFormat {
+                size: AttributeSize::$size,
+                type_: AttributeType::$type_,
+                normalized: false,
+            };
+        }
+    };
+}
+
+vertex_attribute!(f32, Float);
+vertex_attribute!(i8, Byte);
+vertex_attribute!(u8, UnsignedByte);
+vertex_attribute!(i16, Short);
+vertex_attribute!(u16, UnsignedShort);
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use bytemuck::{Pod, Zeroable};
+
+    use super::*;
+
+    const SHORTS: AttributeFormat = AttributeFormat {
+        size: AttributeSize::TWO,
+        type_: AttributeType::Short,
+        normalized: false,
+    };
+
+    #[derive(Clone, Copy, Pod, Zeroable, Vertex)]
+    #[repr(C, align(16))]
+    struct Aligned {
+        pos: [f32; 3],
+        #[gl(normalized)]
+        color: [u8; 4],
+        #[gl(format = SHORTS)]
+        uv: [i16; 2],
+        #[gl(format = SHORTS, normalized)]
+        normal: [i16; 2],
+        weights: [f32; 2],
+    }
+
+    #[test]
+    fn repr_list_and_formats() {
+        let formats: Vec<_> = Aligned::FIELDS
+            .iter()
+            .map(|f| (f.name, f.format.type_, f.format.normalized, f.offset))
+            .collect();
+        assert_eq!(
+            formats,
+            [
+                ("pos", AttributeType::Float, false, 0),
+                ("color", AttributeType::UnsignedByte, true, 12),
+                ("uv", AttributeType::Short, false, 16),
+                ("normal", AttributeType::Short, true, 20),
+                ("weights", AttributeType::Float, false, 24),
+            ]
+        );
+        assert_eq!(align_of::<Aligned>(), 16);
+    }
+}
@@ -0,0 +1,785 @@
+use std::collections::HashSet;
+
+use crate::lexer::{Token, TokenKind};
+
+/// A problem at a line, before the stage is known.
+pub type Error = (u32, String);
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Ident(String, u32),
+    Literal(String),
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        line: u32,
+    },
+    Index(Box<Expr>, Box<Expr>),
+    Field(Box<Expr>, String),
+    Prefix(&'static str, Box<Expr>),
+    Postfix(Box<Expr>, &'static str),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    Assign(Box<Expr>, &'static str, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Paren(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct VarDecl {
+    pub name: String,
+    pub array: Option<Expr>,
+    pub init: Option<Expr>,
+    pub line: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub constant: bool,
+    pub ty: String,
+    pub vars: Vec<VarDecl>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt {
+    Directive(String),
+    Decl(Declaration),
+    Expr(Expr),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    For(Option<Box<Stmt>>, Option<Expr>, Option<Expr>, Box<Stmt>),
+    While(Expr, Box<Stmt>),
+    DoWhile(Box<Stmt>, Expr),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Discard,
+    Empty,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    /// `in`, `out` or `inout`.
+    pub direction: Option<&'static str>,
+    pub constant: bool,
+    pub ty: String,
+    pub name: Option<String>,
+    pub array: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Item {
+    Directive(String),
+    /// `attribute`, `varying`, `uniform`, `const` or nothing.
+    Global {
+        storage: Option<&'static str>,
+        decl: Declaration,
+        line: u32,
+    },
+    Struct {
+        name: String,
+        members: Vec<Declaration>,
+    },
+    Function {
+        return_type: String,
+        name: String,
+        params: Vec<Param>,
+        /// `None` for a prototype.
+        body: Option<Vec<Stmt>>,
+        line: u32,
+    },
+}
+
+pub const BUILTIN_TYPES: &[&str] = &[
+    "void",
+    "float",
+    "int",
+    "bool",
+    "vec2",
+    "vec3",
+    "vec4",
+    "ivec2",
+    "ivec3",
+    "ivec4",
+    "bvec2",
+    "bvec3",
+    "bvec4",
+    "mat2",
+    "mat3",
+    "mat4",
+    "sampler2D",
+    "samplerCube",
+];
+
+const PRECISIONS: &[&str] = &["lowp", "mediump", "highp"];
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    structs: HashSet<String>,
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Self {
+        // Cg has no precision qualifiers, and they never change meaning.
+        let tokens = tokens
+            .into_iter()
+            .filter(|t| !t.ident().is_some_and(|i| PRECISIONS.contains(&i)))
+            .collect();
+        Parser {
+            tokens,
+            pos: 0,
+            structs: HashSet::new(),
+        }
+    }
+
+    pub fn items(&mut self) -> Result<Vec<Item>, Error> {
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            let line = token.line;
+            if let TokenKind::Directive(d) = &token.kind {
+                items.push(Item::Directive(d.clone()));
+                self.pos += 1;
+                continue;
+            }
+            if self.eat_ident("precision") {
+                while !self.eat_punct(';') {
+                    self.next()?;
+                }
+                continue;
+            }
+            if self.eat_punct(';') {
+                continue;
+            }
+            if self.eat_ident("struct") {
+                items.push(self.struct_def()?);
+                continue;
+            }
+            self.eat_ident("invariant");
+            let storage = ["attribute", "varying", "uniform", "const"]
+                .into_iter()
+                .find(|s| self.eat_ident(s));
+            let ty = self.type_name()?;
+            let (name, name_line) = self.ident()?;
+            if storage.is_none() && self.eat_punct('(') {
+                items.push(self.function(ty, name, line)?);
+                continue;
+            }
+            let vars = self.declarators(name, name_line)?;
+            items.push(Item::Global {
+                storage,
+                decl: Declaration {
+                    constant: storage == Some("const"),
+                    ty,
+                    vars,
+                },
+                line,
+            });
+        }
+        Ok(items)
+    }
+
+    fn struct_def(&mut self) -> Result<Item, Error> {
+        let (name, _) = self.ident()?;
+        self.expect_punct('{')?;
+        let mut members = Vec::new();
+        while !self.eat_punct('}') {
+            let ty = self.type_name()?;
+            let (first, line) = self.ident()?;
+            members.push(Declaration {
+                constant: false,
+                ty,
+                vars: self.declarators(first, line)?,
+            });
+        }
+        if !self.eat_punct(';') {
+            return Err((
+                self.line(),
+                "declaring variables along with a struct isn't supported".into(),
+            ));
+        }
+        self.structs.insert(name.clone());
+        Ok(Item::Struct { name, members })
+    }
+
+    fn function(&mut self, return_type: String, name: String, line: u32) -> Result<Item, Error> {
+        let mut params = Vec::new();
+        if !self.eat_punct(')') {
+            if self.peek_ident() == Some("void") && self.peek_punct_at(1, ')') {
+                self.pos += 2;
+            } else {
+                loop {
+                    params.push(self.param()?);
+                    if self.eat_punct(')') {
+                        break;
+                    }
+                    self.expect_punct(',')?;
+                }
+            }
+        }
+        let body = if self.eat_punct(';') {
+            None
+        } else {
+            self.expect_punct('{')?;
+            Some(self.block()?)
+        };
+        Ok(Item::Function {
+            return_type,
+            name,
+            params,
+            body,
+            line,
+        })
+    }
+
+    fn param(&mut self) -> Result<Param, Error> {
+        let constant = self.eat_ident("const");
+        let direction = ["inout", "in", "out"]
+            .into_iter()
+            .find(|d| self.eat_ident(d));
+        let ty = self.type_name()?;
+        let name = match self.peek_ident() {
+            Some(_) => Some(self.ident()?.0),
+            None => None,
+        };
+        let array = if self.eat_punct('[') {
+            let len = self.expr()?;
+            self.expect_punct(']')?;
+            Some(len)
+        } else {
+            None
+        };
+        Ok(Param {
+            direction,
+            constant,
+            ty,
+            name,
+            array,
+        })
+    }
+
+    /// `name[len] = init, name2 ... ;` after the type, with the first name already read.
+    fn declarators(&mut self, mut name: String, mut line: u32) -> Result<Vec<VarDecl>, Error> {
+        let mut vars = Vec::new();
+        loop {
+            let array = if self.eat_punct('[') {
+                let len = self.expr()?;
+                self.expect_punct(']')?;
+                Some(len)
+            } else {
+                None
+            };
+            let init = if self.eat_punct('=') {
+                Some(self.assignment()?)
+            } else {
+                None
+            };
+            vars.push(VarDecl {
+                name,
+                array,
+                init,
+                line,
+            });
+            if self.eat_punct(';') {
+                return Ok(vars);
+            }
+            self.expect_punct(',')?;
+            (name, line) = self.ident()?;
+        }
+    }
+
+    fn type_name(&mut self) -> Result<String, Error> {
+        let line = self.line();
+        match self.peek_ident() {
+            Some(ty) if BUILTIN_TYPES.contains(&ty) || self.structs.contains(ty) => {
+                Ok(self.ident()?.0)
+            }
+            Some(other) => Err((line, format!("unknown type `{other}`"))),
+            None => Err((line, "expected a type".into())),
+        }
+    }
+
+    fn is_declaration(&self) -> bool {
+        let first = match self.peek_ident() {
+            Some("const") => return true,
+            Some(first) => first,
+            None => return false,
+        };
+        (BUILTIN_TYPES.contains(&first) || self.structs.contains(first))
+            && self
+                .tokens
+                .get(self.pos + 1)
+                .is_some_and(|t| t.ident().is_some())
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        let mut stmts = Vec::new();
+        while !self.eat_punct('}') {
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        let token = self.next()?.clone();
+        if let TokenKind::Directive(d) = &token.kind {
+            return Ok(Stmt::Directive(d.clone()));
+        }
+        if token.is_punct('{') {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if token.is_punct(';') {
+            return Ok(Stmt::Empty);
+        }
+        match token.ident() {
+            Some("if") => {
+                let cond = self.paren_expr()?;
+                let then = Box::new(self.statement()?);
+                let otherwise = if self.eat_ident("else") {
+                    Some(Box::new(self.statement()?))
+                } else {
+                    None
+                };
+                return Ok(Stmt::If(cond, then, otherwise));
+            }
+            Some("for") => {
+                self.expect_punct('(')?;
+                let init = if self.eat_punct(';') {
+                    None
+                } else {
+                    Some(Box::new(self.simple_statement()?))
+                };
+                let cond = self.optional_expr(';')?;
+                let step = self.optional_expr(')')?;
+                return Ok(Stmt::For(init, cond, step, Box::new(self.statement()?)));
+            }
+            Some("while") => {
+                let cond = self.paren_expr()?;
+                return Ok(Stmt::While(cond, Box::new(self.statement()?)));
+            }
+            Some("do") => {
+                let body = Box::new(self.statement()?);
+                if !self.eat_ident("while") {
+                    return Err((self.line(), "expected `while`".into()));
+                }
+                let cond = self.paren_expr()?;
+                self.expect_punct(';')?;
+                return Ok(Stmt::DoWhile(body, cond));
+            }
+            Some("return") => return Ok(Stmt::Return(self.optional_expr(';')?)),
+            Some("break") => {
+                self.expect_punct(';')?;
+                return Ok(Stmt::Break);
+            }
+            Some("continue") => {
+                self.expect_punct(';')?;
+                return Ok(Stmt::Continue);
+            }
+            Some("discard") => {
+                self.expect_punct(';')?;
+                return Ok(Stmt::Discard);
+            }
+            _ => {}
+        }
+        self.pos -= 1;
+        self.simple_statement()
+    }
+
+    /// A declaration or expression, with its `;`.
+    fn simple_statement(&mut self) -> Result<Stmt, Error> {
+        if self.is_declaration() {
+            let constant = self.eat_ident("const");
+            let ty = self.type_name()?;
+            let (name, line) = self.ident()?;
+            return Ok(Stmt::Decl(Declaration {
+                constant,
+                ty,
+                vars: self.declarators(name, line)?,
+            }));
+        }
+        let expr = self.expr()?;
+        self.expect_punct(';')?;
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn optional_expr(&mut self, end: char) -> Result<Option<Expr>, Error> {
+        if self.eat_punct(end) {
+            return Ok(None);
+        }
+        let expr = self.expr()?;
+        self.expect_punct(end)?;
+        Ok(Some(expr))
+    }
+
+    fn paren_expr(&mut self) -> Result<Expr, Error> {
+        self.expect_punct('(')?;
+        let expr = self.expr()?;
+        self.expect_punct(')')?;
+        Ok(expr)
+    }
+
+    pub fn expr(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.assignment()?;
+        while self.eat_punct(',') {
+            expr = Expr::Binary(Box::new(expr), ",", Box::new(self.assignment()?));
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, Error> {
+        let lhs = self.ternary()?;
+        const ASSIGN: &[&str] = &["=", "+=", "-=", "*=", "/="];
+        match self.peek().and_then(op_str) {
+            Some(op) if ASSIGN.contains(&op) => {
+                self.pos += 1;
+                let rhs = self.assignment()?;
+                Ok(Expr::Assign(Box::new(lhs), op, Box::new(rhs)))
+            }
+            _ => Ok(lhs),
+        }
+    }
+
+    fn ternary(&mut self) -> Result<Expr, Error> {
+        let cond = self.binary(0)?;
+        if !self.eat_punct('?') {
+            return Ok(cond);
+        }
+        let then = self.assignment()?;
+        self.expect_punct(':')?;
+        let otherwise = self.assignment()?;
+        Ok(Expr::Ternary(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        const LEVELS: &[&[&str]] = &[
+            &["||"],
+            &["^^"],
+            &["&&"],
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", ">", "<=", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek().and_then(op_str).filter(|op| ops.contains(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        match self.peek().and_then(op_str) {
+            Some(op @ ("-" | "+" | "!" | "~" | "++" | "--")) => {
+                self.pos += 1;
+                Ok(Expr::Prefix(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat_punct('[') {
+                let index = self.expr()?;
+                self.expect_punct(']')?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else if self.eat_punct('.') {
+                expr = Expr::Field(Box::new(expr), self.ident()?.0);
+            } else if let Some(op @ ("++" | "--")) = self.peek().and_then(op_str) {
+                self.pos += 1;
+                expr = Expr::Postfix(Box::new(expr), op);
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let token = self.next()?.clone();
+        match &token.kind {
+            TokenKind::Number(n) => Ok(Expr::Literal(n.clone())),
+            TokenKind::Ident(name) if self.eat_punct('(') => {
+                let mut args = Vec::new();
+                if self.peek_ident() == Some("void") && self.peek_punct_at(1, ')') {
+                    self.pos += 1;
+                }
+                if !self.eat_punct(')') {
+                    loop {
+                        args.push(self.assignment()?);
+                        if self.eat_punct(')') {
+                            break;
+                        }
+                        self.expect_punct(',')?;
+                    }
+                }
+                Ok(Expr::Call {
+                    name: name.clone(),
+                    args,
+                    line: token.line,
+                })
+            }
+            TokenKind::Ident(name) => Ok(Expr::Ident(name.clone(), token.line)),
+            TokenKind::Punct('(') => {
+                let inner = self.expr()?;
+                self.expect_punct(')')?;
+                Ok(Expr::Paren(Box::new(inner)))
+            }
+            _ => Err((
+                token.line,
+                format!("expected an expression, found `{token}`"),
+            )),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn peek_ident(&self) -> Option<&str> {
+        self.peek().and_then(Token::ident)
+    }
+    fn peek_punct_at(&self, offset: usize, c: char) -> bool {
+        self.tokens
+            .get(self.pos + offset)
+            .is_some_and(|t| t.is_punct(c))
+    }
+    fn next(&mut self) -> Result<&Token, Error> {
+        let line = self.line();
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| (line, "unexpected end of the shader".to_string()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+    fn line(&self) -> u32 {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |t| t.line)
+    }
+    fn eat_punct(&mut self, c: char) -> bool {
+        let eaten = self.peek().is_some_and(|t| t.is_punct(c));
+        self.pos += eaten as usize;
+        eaten
+    }
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        let eaten = self.peek_ident() == Some(ident);
+        self.pos += eaten as usize;
+        eaten
+    }
+    fn expect_punct(&mut self, c: char) -> Result<(), Error> {
+        if self.eat_punct(c) {
+            return Ok(());
+        }
+        let found = self
+            .peek()
+            .map_or("the end of the shader".to_string(), |t| format!("`{t}`"));
+        Err((self.line(), format!("expected `{c}`, found {found}")))
+    }
+    fn ident(&mut self) -> Result<(String, u32), Error> {
+        let line = self.line();
+        match self.peek_ident() {
+            Some(ident) => {
+                let ident = ident.to_string();
+                self.pos += 1;
+                Ok((ident, line))
+            }
+            None => Err((line, "expected a name".into())),
+        }
+    }
+}
+
+/// The operator a token stands for, if it is one.
+fn op_str(token: &Token) -> Option<&'static str> {
+    match token.kind {
+        TokenKind::Op(op) => Some(op),
+        TokenKind::Punct(c) => [
+            "+", "-", "*", "/", "%", "<", ">", "=", "!", "~", "&", "|", "^",
+        ]
+        .into_iter()
+        .find(|op| op.starts_with(c)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer;
+
+    fn parse(source: &str) -> Result<Vec<Item>, Error> {
+        Parser::new(lexer::tokenize(source).unwrap()).items()
+    }
+
+    fn parse_expr(source: &str) -> Expr {
+        Parser::new(lexer::tokenize(source).unwrap())
+            .expr()
+            .unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        parse(source).unwrap_err().1
+    }
+
+    #[test]
+    fn globals_and_precision() {
+        let items = parse(
+            "precision highp float;
+            attribute lowp vec2 aPos;
+            varying vec2 vUv, vPos;
+            uniform mat4 mvp;
+            const float scale[2] = 0.5;",
+        )
+        .unwrap();
+        let globals: Vec<_> = items
+            .iter()
+            .map(|item| match item {
+                Item::Global { storage, decl, .. } => {
+                    let names: Vec<&str> = decl.vars.iter().map(|v| v.name.as_str()).collect();
+                    (*storage, decl.ty.as_str(), names)
+                }
+                item => panic!("not a global: {item:?}"),
+            })
+            .collect();
+        assert_eq!(
+            globals,
+            [
+                (Some("attribute"), "vec2", vec!["aPos"]),
+                (Some("varying"), "vec2", vec!["vUv", "vPos"]),
+                (Some("uniform"), "mat4", vec!["mvp"]),
+                (Some("const"), "float", vec!["scale"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn structs_and_functions() {
+        let items = parse(
+            "struct Light { vec3 pos; float power; };
+            Light first(Light lights[2]);
+            vec3 tint(in vec3 c, const float k) { return c * k; }
+            void main(void) {}",
+        )
+        .unwrap();
+        assert!(
+            matches!(&items[0], Item::Struct { name, members } if name == "Light" && members.len() == 2)
+        );
+        assert!(
+            matches!(&items[1], Item::Function { body: None, params, .. }
+            if params[0].ty == "Light" && params[0].array.is_some())
+        );
+        assert!(
+            matches!(&items[2], Item::Function { name, params, body: Some(_), .. }
+            if name == "tint" && params[0].direction == Some("in") && params[1].constant)
+        );
+        assert!(matches!(&items[3], Item::Function { params, .. } if params.is_empty()));
+    }
+
+    #[test]
+    fn statements() {
+        let items = parse(
+            "void main() {
+                for (int i = 0; i < 4; i++) { if (i == 2) continue; else break; }
+                while (true) discard;
+                do { } while (false);
+                ;
+                return;
+            }",
+        )
+        .unwrap();
+        let Item::Function {
+            body: Some(body), ..
+        } = &items[0]
+        else {
+            panic!("not a function");
+        };
+        assert!(
+            matches!(&body[0], Stmt::For(Some(init), Some(_), Some(Expr::Postfix(_, "++")), _)
+            if matches!(**init, Stmt::Decl(_)))
+        );
+        assert!(matches!(&body[1], Stmt::While(_, discard) if matches!(**discard, Stmt::Discard)));
+        assert!(matches!(&body[2], Stmt::DoWhile(..)));
+        assert!(matches!(&body[3], Stmt::Empty));
+        assert!(matches!(&body[4], Stmt::Return(None)));
+    }
+
+    /// `expr` with every operation parenthesised.
+    fn show(expr: &Expr) -> String {
+        match expr {
+            Expr::Ident(name, _) | Expr::Literal(name) => name.clone(),
+            Expr::Call { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(show).collect();
+                format!("{name}({})", args.join(", "))
+            }
+            Expr::Index(base, index) => format!("{}[{}]", show(base), show(index)),
+            Expr::Field(base, field) => format!("{}.{field}", show(base)),
+            Expr::Prefix(op, operand) => format!("({op}{})", show(operand)),
+            Expr::Postfix(operand, op) => format!("({}{op})", show(operand)),
+            Expr::Binary(l, op, r) | Expr::Assign(l, op, r) => {
+                format!("({} {op} {})", show(l), show(r))
+            }
+            Expr::Ternary(c, t, o) => format!("({} ? {} : {})", show(c), show(t), show(o)),
+            Expr::Paren(inner) => show(inner),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        let cases = [
+            ("a += b * c + d", "(a += ((b * c) + d))"),
+            ("a || b ^^ c && d", "(a || (b ^^ (c && d)))"),
+            ("a == b < c", "(a == (b < c))"),
+            ("a = b = c", "(a = (b = c))"),
+            ("c ? x : y = z", "(c ? x : (y = z))"),
+            ("-v.xy[1]++", "(-(v.xy[1]++))"),
+            ("(a + b) * f(c, d - e)", "((a + b) * f(c, (d - e)))"),
+            ("a, b", "(a , b)"),
+        ];
+        for (source, parsed) in cases {
+            assert_eq!(show(&parse_expr(source)), parsed, "{source}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            error("struct S { float x; } s;"),
+            "declaring variables along with a struct isn't supported"
+        );
+        assert_eq!(error("uniform half x;"), "unknown type `half`");
+        assert_eq!(error("uniform 1;"), "expected a type");
+        assert_eq!(error("float 1;"), "expected a name");
+        assert_eq!(
+            error("void main() { do {} until (x); }"),
+            "expected `while`"
+        );
+        assert_eq!(
+            error("void main() { x = ; }"),
+            "expected an expression, found `;`"
+        );
+        assert_eq!(error("void main() {"), "unexpected end of the shader");
+        assert_eq!(
+            error("float x"),
+            "expected `,`, found the end of the shader"
+        );
+        assert_eq!(error("void main() { f(a b); }"), "expected `,`, found `b`");
+    }
+
+    #[test]
+    fn error_lines() {
+        assert_eq!(parse("float a;\n\nvec2 b\nfloat c;").unwrap_err().0, 4);
+    }
+}
@@ -0,0 +1,1253 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::Stage;
+use super::ast::{Declaration, Error, Expr, Item, Param, Stmt};
+
+/// What the translator knows about the type of an expression. Only matrices need to be
+/// told apart for correctness; the rest is for picking result types.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Float(u8),
+    Int(u8),
+    Bool(u8),
+    Mat(u8),
+    Sampler,
+    Struct(String),
+    Array(Box<Ty>),
+    Void,
+    Unknown,
+}
+
+impl Ty {
+    fn from_glsl(name: &str) -> Ty {
+        let dim = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|n| (2..=4).contains(n))
+        };
+        match name {
+            "float" => Ty::Float(1),
+            "int" => Ty::Int(1),
+            "bool" => Ty::Bool(1),
+            "void" => Ty::Void,
+            "sampler2D" | "samplerCube" => Ty::Sampler,
+            _ => {
+                if let Some(n) = dim("vec") {
+                    Ty::Float(n)
+                } else if let Some(n) = dim("ivec") {
+                    Ty::Int(n)
+                } else if let Some(n) = dim("bvec") {
+                    Ty::Bool(n)
+                } else if let Some(n) = dim("mat") {
+                    Ty::Mat(n)
+                } else {
+                    Ty::Struct(name.to_string())
+                }
+            }
+        }
+    }
+    fn width(&self) -> u8 {
+        match self {
+            Ty::Float(n) | Ty::Int(n) | Ty::Bool(n) => *n,
+            _ => 0,
+        }
+    }
+    fn with_width(&self, n: u8) -> Ty {
+        match self {
+            Ty::Int(_) => Ty::Int(n),
+            Ty::Bool(_) => Ty::Bool(n),
+            _ => Ty::Float(n),
+        }
+    }
+    fn is_scalar(&self) -> bool {
+        self.width() == 1
+    }
+}
+
+/// The result type of a component-wise operation on `tys`.
+fn widest<'a>(tys: impl IntoIterator<Item = &'a Ty>) -> Ty {
+    let mut result = Ty::Unknown;
+    for ty in tys {
+        result = match (&result, ty) {
+            (Ty::Unknown, _) | (_, Ty::Mat(_)) => ty.clone(),
+            (Ty::Mat(_), _) => result,
+            (Ty::Int(a), Ty::Float(b)) | (Ty::Float(a), Ty::Int(b)) => Ty::Float(*a.max(b)),
+            (a, b) if b.width() > a.width() => b.clone(),
+            _ => result,
+        };
+    }
+    result
+}
+
+/// The Cg spelling of a GLSL type name.
+fn cg_type(name: &str) -> String {
+    match Ty::from_glsl(name) {
+        Ty::Float(n) if n > 1 => format!("float{n}"),
+        Ty::Int(n) if n > 1 => format!("int{n}"),
+        Ty::Bool(n) if n > 1 => format!("bool{n}"),
+        Ty::Mat(n) => format!("float{n}x{n}"),
+        _ if name == "samplerCube" => "samplerCUBE".into(),
+        _ => name.into(),
+    }
+}
+
+/// GLSL built-ins that only differ from Cg in name.
+const RENAMED: &[(&str, &str)] = &[
+    ("mix", "lerp"),
+    ("fract", "frac"),
+    ("inversesqrt", "rsqrt"),
+    ("dFdx", "ddx"),
+    ("dFdy", "ddy"),
+    ("texture2D", "tex2D"),
+    ("texture2DProj", "tex2Dproj"),
+    ("textureCube", "texCUBE"),
+];
+
+/// GLSL built-ins with the same name and meaning in Cg.
+const SAME: &[&str] = &[
+    "radians",
+    "degrees",
+    "sin",
+    "cos",
+    "tan",
+    "asin",
+    "acos",
+    "atan",
+    "pow",
+    "exp",
+    "log",
+    "exp2",
+    "log2",
+    "sqrt",
+    "abs",
+    "sign",
+    "floor",
+    "ceil",
+    "min",
+    "max",
+    "clamp",
+    "step",
+    "smoothstep",
+    "normalize",
+    "faceforward",
+    "reflect",
+    "refract",
+    "fwidth",
+];
+
+const COMPARISONS: &[(&str, &str)] = &[
+    ("lessThan", "<"),
+    ("lessThanEqual", "<="),
+    ("greaterThan", ">"),
+    ("greaterThanEqual", ">="),
+    ("equal", "=="),
+    ("notEqual", "!="),
+];
+
+/// Words Cg reserves that are plain identifiers in GLSL.
+const CG_RESERVED: &[&str] = &[
+    "half",
+    "fixed",
+    "static",
+    "inline",
+    "interface",
+    "string",
+    "texture",
+    "sampler",
+    "packed",
+    "typedef",
+    "register",
+    "compile",
+    "technique",
+    "pass",
+    "asm",
+    "snorm",
+    "unorm",
+];
+
+/// Wraps `s` in parentheses unless it is a single name, number or call.
+fn atom(s: &str) -> String {
+    let simple = s
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '.');
+    if simple {
+        s.to_string()
+    } else {
+        format!("({s})")
+    }
+}
+
+/// Rewrites type and built-in names in a preprocessor line. Nothing else can be
+/// translated without expanding macros.
+fn directive(d: &str) -> Option<String> {
+    let keyword = d.split_whitespace().next().unwrap_or("");
+    if matches!(keyword, "version" | "extension") {
+        return None;
+    }
+    let mut out = String::from("#");
+    let mut word = String::new();
+    for c in d.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            let renamed = RENAMED.iter().find(|(glsl, _)| *glsl == word);
+            match renamed {
+                Some((_, cg)) => out.push_str(cg),
+                None if super::ast::BUILTIN_TYPES.contains(&word.as_str()) => {
+                    out.push_str(&cg_type(&word))
+                }
+                None => out.push_str(&word),
+            }
+            word.clear();
+        }
+        out.push(c);
+    }
+    out.pop();
+    Some(out)
+}
+
+/// A varying or attribute, as an entry point parameter.
+struct Interface {
+    ty: String,
+    name: String,
+}
+
+pub struct Emitter<'a> {
+    stage: Stage,
+    /// Varyings in the order of their `TEXCOORD` semantics, if fixed by the vertex shader.
+    known_varyings: Option<&'a [String]>,
+    attributes: Vec<Interface>,
+    varyings: Vec<Interface>,
+    /// `gl_PointSize`, `gl_FragCoord` and `gl_PointCoord`, when used.
+    builtins: BTreeSet<&'static str>,
+    structs: HashMap<String, HashMap<String, Ty>>,
+    functions: HashMap<String, Ty>,
+    scopes: Vec<HashMap<String, Ty>>,
+    in_main: bool,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(stage: Stage, known_varyings: Option<&'a [String]>) -> Self {
+        Emitter {
+            stage,
+            known_varyings,
+            attributes: Vec::new(),
+            varyings: Vec::new(),
+            builtins: BTreeSet::new(),
+            structs: HashMap::new(),
+            functions: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            in_main: false,
+        }
+    }
+
+    /// The Cg source, and the varyings in semantic order.
+    pub fn emit(mut self, items: &[Item]) -> Result<(String, Vec<String>), Error> {
+        let mut out = String::new();
+        let mut has_main = false;
+        for item in items {
+            match item {
+                Item::Directive(d) => {
+                    if let Some(d) = directive(d) {
+                        out.push_str(&d);
+                        out.push('\n');
+                    }
+                }
+                Item::Global {
+                    storage,
+                    decl,
+                    line,
+                } => self.global(*storage, decl, *line, &mut out)?,
+                Item::Struct { name, members } => {
+                    let _ = writeln!(out, "struct {name} {{");
+                    self.scopes.push(HashMap::new());
+                    for member in members {
+                        let decl = self.declaration(member)?;
+                        let _ = writeln!(out, "    {decl};");
+                    }
+                    out.push_str("};\n");
+                    let fields = self.scopes.pop().expect("pushed above");
+                    self.structs.insert(name.clone(), fields);
+                }
+                Item::Function {
+                    name, body: None, ..
+                } if name == "main" => {}
+                Item::Function {
+                    return_type,
+                    name,
+                    params,
+                    body,
+                    line,
+                } => {
+                    if name == "main" {
+                        has_main = true;
+                        self.main(
+                            return_type,
+                            params,
+                            body.as_deref().unwrap_or(&[]),
+                            *line,
+                            &mut out,
+                        )?;
+                    } else {
+                        self.function(return_type, name, params, body.as_deref(), *line, &mut out)?;
+                    }
+                }
+            }
+        }
+        if !has_main {
+            return Err((1, "the shader has no `main`".into()));
+        }
+        let varyings = self.varyings.into_iter().map(|v| v.name).collect();
+        Ok((out, varyings))
+    }
+
+    fn global(
+        &mut self,
+        storage: Option<&'static str>,
+        decl: &Declaration,
+        line: u32,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        match storage {
+            Some(kind @ ("attribute" | "varying")) => {
+                if kind == "attribute" && self.stage == Stage::Fragment {
+                    return Err((line, "attributes are only allowed in vertex shaders".into()));
+                }
+                let ty = Ty::from_glsl(&decl.ty);
+                if matches!(ty, Ty::Mat(_)) {
+                    return Err((line, format!("{kind} matrices aren't supported")));
+                }
+                for var in &decl.vars {
+                    if var.array.is_some() {
+                        return Err((var.line, format!("{kind} arrays aren't supported")));
+                    }
+                    self.declare(&var.name, ty.clone(), var.line)?;
+                    let interface = Interface {
+                        ty: cg_type(&decl.ty),
+                        name: var.name.clone(),
+                    };
+                    if kind == "attribute" {
+                        self.attributes.push(interface);
+                    } else {
+                        self.varyings.push(interface);
+                    }
+                }
+                Ok(())
+            }
+            _ => {
+                let decl = self.declaration(decl)?;
+                let prefix = match storage {
+                    Some("uniform") => "uniform ",
+                    _ => "static ",
+                };
+                let _ = writeln!(out, "{prefix}{decl};");
+                Ok(())
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        return_type: &str,
+        name: &str,
+        params: &[Param],
+        body: Option<&[Stmt]>,
+        line: u32,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        if !self.functions.contains_key(name) {
+            self.check_name(name, line)?;
+        }
+        self.functions
+            .insert(name.to_string(), Ty::from_glsl(return_type));
+        self.scopes.push(HashMap::new());
+        let mut cg_params = Vec::new();
+        for param in params {
+            cg_params.push(self.param(param, line)?);
+        }
+        let _ = write!(
+            out,
+            "{} {name}({})",
+            cg_type(return_type),
+            cg_params.join(", ")
+        );
+        match body {
+            None => out.push_str(";\n"),
+            Some(body) => {
+                out.push_str(" {\n");
+                for stmt in body {
+                    self.stmt(stmt, 1, out)?;
+                }
+                out.push_str("}\n");
+            }
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn param(&mut self, param: &Param, line: u32) -> Result<String, Error> {
+        let mut s = String::new();
+        if let Some(direction @ ("out" | "inout")) = param.direction {
+            s.push_str(direction);
+            s.push(' ');
+        }
+        if param.constant {
+            s.push_str("const ");
+        }
+        s.push_str(&cg_type(&param.ty));
+        if let Some(name) = &param.name {
+            let mut ty = Ty::from_glsl(&param.ty);
+            s.push(' ');
+            s.push_str(name);
+            if let Some(len) = &param.array {
+                let _ = write!(s, "[{}]", self.expr(len)?.0);
+                ty = Ty::Array(Box::new(ty));
+            }
+            self.declare(name, ty, line)?;
+        }
+        Ok(s)
+    }
+
+    fn main(
+        &mut self,
+        return_type: &str,
+        params: &[Param],
+        body: &[Stmt],
+        line: u32,
+        out: &mut String,
+    ) -> Result<(), Error> {
+        if return_type != "void" || !params.is_empty() {
+            return Err((line, "`main` must be `void main()`".into()));
+        }
+        self.in_main = true;
+        self.scopes.push(HashMap::new());
+        let mut body_text = String::new();
+        for stmt in body {
+            self.stmt(stmt, 1, &mut body_text)?;
+        }
+        self.scopes.pop();
+        self.in_main = false;
+
+        let mut cg_params = Vec::new();
+        match self.stage {
+            Stage::Vertex => {
+                for a in &self.attributes {
+                    cg_params.push(format!("{} {}", a.ty, a.name));
+                }
+                cg_params.push("float4 out gl_Position : POSITION".into());
+                if self.builtins.contains("gl_PointSize") {
+                    cg_params.push("float out gl_PointSize : PSIZE".into());
+                }
+                for (i, v) in self.varyings.iter().enumerate() {
+                    cg_params.push(format!("{} out {} : TEXCOORD{i}", v.ty, v.name));
+                }
+            }
+            Stage::Fragment => {
+                for v in &self.varyings {
+                    let index = match self.known_varyings {
+                        None => self.varyings.iter().position(|o| o.name == v.name),
+                        Some(known) => known.iter().position(|k| *k == v.name),
+                    }
+                    .ok_or_else(|| {
+                        (
+                            line,
+                            format!("varying `{}` isn't written by the vertex shader", v.name),
+                        )
+                    })?;
+                    cg_params.push(format!("{} {} : TEXCOORD{index}", v.ty, v.name));
+                }
+                if self.builtins.contains("gl_FragCoord") {
+                    cg_params.push("float4 gl_FragCoord : WPOS".into());
+                }
+                if self.builtins.contains("gl_PointCoord") {
+                    cg_params.push("float2 gl_PointCoord : SPRITECOORD".into());
+                }
+                cg_params.push("float4 out gl_FragColor : COLOR".into());
+            }
+        }
+        if self.varyings.len() > 10 {
+            return Err((line, "Cg allows at most 10 varyings".into()));
+        }
+        let _ = writeln!(out, "void main(\n    {}\n) {{", cg_params.join(",\n    "));
+        out.push_str(&body_text);
+        out.push_str("}\n");
+        Ok(())
+    }
+
+    fn check_name(&self, name: &str, line: u32) -> Result<(), Error> {
+        let cg_builtin = !matches!(
+            crate::CgType::parse(name),
+            crate::CgType::Other(_) | crate::CgType::Sampler2D | crate::CgType::SamplerCube
+        );
+        if CG_RESERVED.contains(&name) || cg_builtin {
+            return Err((line, format!("`{name}` is a reserved word in Cg")));
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, name: &str, ty: Ty, line: u32) -> Result<(), Error> {
+        self.check_name(name, line)?;
+        self.scopes
+            .last_mut()
+            .expect("there is always a global scope")
+            .insert(name.to_string(), ty);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Ty {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or(Ty::Unknown)
+    }
+
+    /// `[const] T a[n] = x, b`, without the `;`. Declares the variables in the innermost scope.
+    fn declaration(&mut self, decl: &Declaration) -> Result<String, Error> {
+        let mut s = String::new();
+        if decl.constant {
+            s.push_str("const ");
+        }
+        s.push_str(&cg_type(&decl.ty));
+        for (i, var) in decl.vars.iter().enumerate() {
+            s.push_str(if i == 0 { " " } else { ", " });
+            s.push_str(&var.name);
+            let mut ty = Ty::from_glsl(&decl.ty);
+            if let Some(len) = &var.array {
+                let _ = write!(s, "[{}]", self.expr(len)?.0);
+                ty = Ty::Array(Box::new(ty));
+            }
+            if let Some(init) = &var.init {
+                let _ = write!(s, " = {}", self.expr(init)?.0);
+            }
+            self.declare(&var.name, ty, var.line)?;
+        }
+        Ok(s)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize, out: &mut String) -> Result<(), Error> {
+        let indent = "    ".repeat(depth);
+        match stmt {
+            Stmt::Directive(d) => {
+                if let Some(d) = directive(d) {
+                    out.push_str(&d);
+                    out.push('\n');
+                }
+            }
+            Stmt::Decl(_) | Stmt::Expr(_) => {
+                let s = self.simple(stmt)?;
+                let _ = writeln!(out, "{indent}{s};");
+            }
+            Stmt::Block(stmts) => {
+                let _ = writeln!(out, "{indent}{{");
+                self.scopes.push(HashMap::new());
+                for stmt in stmts {
+                    self.stmt(stmt, depth + 1, out)?;
+                }
+                self.scopes.pop();
+                let _ = writeln!(out, "{indent}}}");
+            }
+            Stmt::If(..) => {
+                out.push_str(&indent);
+                self.if_chain(stmt, depth, out)?;
+                out.push('\n');
+            }
+            Stmt::For(init, cond, step, body) => {
+                self.scopes.push(HashMap::new());
+                let init = match init {
+                    Some(init) => self.simple(init)?,
+                    None => String::new(),
+                };
+                let cond = match cond {
+                    Some(cond) => format!(" {}", self.expr(cond)?.0),
+                    None => String::new(),
+                };
+                let step = match step {
+                    Some(step) => format!(" {}", self.expr(step)?.0),
+                    None => String::new(),
+                };
+                let _ = write!(out, "{indent}for ({init};{cond};{step})");
+                self.braced(body, depth, out)?;
+                self.scopes.pop();
+                out.push('\n');
+            }
+            Stmt::While(cond, body) => {
+                let cond = self.expr(cond)?.0;
+                let _ = write!(out, "{indent}while ({cond})");
+                self.braced(body, depth, out)?;
+                out.push('\n');
+            }
+            Stmt::DoWhile(body, cond) => {
+                let _ = write!(out, "{indent}do");
+                self.braced(body, depth, out)?;
+                let _ = writeln!(out, " while ({});", self.expr(cond)?.0);
+            }
+            Stmt::Return(None) => {
+                let _ = writeln!(out, "{indent}return;");
+            }
+            Stmt::Return(Some(value)) => {
+                let _ = writeln!(out, "{indent}return {};", self.expr(value)?.0);
+            }
+            Stmt::Break => {
+                let _ = writeln!(out, "{indent}break;");
+            }
+            Stmt::Continue => {
+                let _ = writeln!(out, "{indent}continue;");
+            }
+            Stmt::Discard => {
+                let _ = writeln!(out, "{indent}discard;");
+            }
+            Stmt::Empty => {}
+        }
+        Ok(())
+    }
+
+    /// A declaration or expression statement, without the `;`.
+    fn simple(&mut self, stmt: &Stmt) -> Result<String, Error> {
+        match stmt {
+            Stmt::Decl(decl) => self.declaration(decl),
+            Stmt::Expr(expr) => Ok(self.expr(expr)?.0),
+            _ => unreachable!("the parser only produces declarations and expressions here"),
+        }
+    }
+
+    /// `if (c) { ... } else ...`, without leading indentation or a trailing newline.
+    fn if_chain(&mut self, stmt: &Stmt, depth: usize, out: &mut String) -> Result<(), Error> {
+        let Stmt::If(cond, then, otherwise) = stmt else {
+            return self.braced(stmt, depth, out);
+        };
+        let _ = write!(out, "if ({})", self.expr(cond)?.0);
+        self.braced(then, depth, out)?;
+        match otherwise.as_deref() {
+            Some(otherwise @ Stmt::If(..)) => {
+                out.push_str(" else ");
+                self.if_chain(otherwise, depth, out)?;
+            }
+            Some(otherwise) => {
+                out.push_str(" else");
+                self.braced(otherwise, depth, out)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// ` { ... }`, always braced so single statements can't be misread.
+    fn braced(&mut self, stmt: &Stmt, depth: usize, out: &mut String) -> Result<(), Error> {
+        out.push_str(" {\n");
+        self.scopes.push(HashMap::new());
+        match stmt {
+            Stmt::Block(stmts) => {
+                for stmt in stmts {
+                    self.stmt(stmt, depth + 1, out)?;
+                }
+            }
+            stmt => self.stmt(stmt, depth + 1, out)?,
+        }
+        self.scopes.pop();
+        let _ = write!(out, "{}}}", "    ".repeat(depth));
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(String, Ty), Error> {
+        Ok(match expr {
+            Expr::Literal(n) => {
+                let hex = n.starts_with("0x") || n.starts_with("0X");
+                let float = n.contains('.') || (!hex && n.contains(['e', 'E']));
+                (n.clone(), if float { Ty::Float(1) } else { Ty::Int(1) })
+            }
+            Expr::Ident(name, line) => self.ident(name, *line)?,
+            Expr::Paren(inner) => {
+                let (s, ty) = self.expr(inner)?;
+                (format!("({s})"), ty)
+            }
+            Expr::Call { name, args, line } => self.call(name, args, *line)?,
+            Expr::Index(base, index) => {
+                let (base, ty) = self.expr(base)?;
+                let (index, _) = self.expr(index)?;
+                let ty = match ty {
+                    Ty::Array(inner) => *inner,
+                    Ty::Mat(n) => Ty::Float(n),
+                    ty if ty.width() > 1 => ty.with_width(1),
+                    _ => Ty::Unknown,
+                };
+                (format!("{base}[{index}]"), ty)
+            }
+            Expr::Field(base, field) => {
+                let (base, ty) = self.expr(base)?;
+                let ty = match &ty {
+                    Ty::Struct(name) => self
+                        .structs
+                        .get(name)
+                        .and_then(|fields| fields.get(field))
+                        .cloned()
+                        .unwrap_or(Ty::Unknown),
+                    Ty::Float(_) | Ty::Int(_) | Ty::Bool(_) => ty.with_width(field.len() as u8),
+                    _ => Ty::Unknown,
+                };
+                (format!("{base}.{field}"), ty)
+            }
+            Expr::Prefix(op, operand) => {
+                let (s, ty) = self.expr(operand)?;
+                let ty = if *op == "!" { Ty::Bool(1) } else { ty };
+                (format!("{op}{s}"), ty)
+            }
+            Expr::Postfix(operand, op) => {
+                let (s, ty) = self.expr(operand)?;
+                (format!("{s}{op}"), ty)
+            }
+            Expr::Binary(lhs, op, rhs) => {
+                let (l, lt) = self.expr(lhs)?;
+                let (r, rt) = self.expr(rhs)?;
+                self.binary(l, lt, op, r, rt)
+            }
+            Expr::Assign(lhs, op, rhs) => {
+                let (l, lt) = self.expr(lhs)?;
+                let (r, rt) = self.expr(rhs)?;
+                if *op == "*=" && matches!(rt, Ty::Mat(_)) && !lt.is_scalar() {
+                    (format!("{l} = mul({r}, {l})"), lt)
+                } else {
+                    (format!("{l} {op} {r}"), lt)
+                }
+            }
+            Expr::Ternary(cond, then, otherwise) => {
+                let (c, _) = self.expr(cond)?;
+                let (t, tt) = self.expr(then)?;
+                let (o, ot) = self.expr(otherwise)?;
+                (format!("{c} ? {t} : {o}"), widest([&tt, &ot]))
+            }
+        })
+    }
+
+    fn binary(&self, l: String, lt: Ty, op: &str, r: String, rt: Ty) -> (String, Ty) {
+        let matrix = matches!(lt, Ty::Mat(_)) || matches!(rt, Ty::Mat(_));
+        match op {
+            // GLSL matrices are column-major and Cg's are row-major, so each one reads as
+            // the other's transpose and the operands swap.
+            "*" if matrix && !lt.is_scalar() && !rt.is_scalar() => {
+                let ty = match (&lt, &rt) {
+                    (Ty::Mat(_), Ty::Mat(_)) => lt.clone(),
+                    (Ty::Mat(_), _) => rt.clone(),
+                    _ => lt.clone(),
+                };
+                (format!("mul({r}, {l})"), ty)
+            }
+            "^^" => (format!("({l} != {r})"), Ty::Bool(1)),
+            "==" | "!=" | "<" | ">" | "<=" | ">=" | "&&" | "||" => {
+                (format!("{l} {op} {r}"), Ty::Bool(1))
+            }
+            "," => (format!("{l}, {r}"), rt),
+            _ => {
+                let ty = widest([&lt, &rt]);
+                (format!("{l} {op} {r}"), ty)
+            }
+        }
+    }
+
+    fn ident(&mut self, name: &str, line: u32) -> Result<(String, Ty), Error> {
+        match name {
+            "true" | "false" => return Ok((name.into(), Ty::Bool(1))),
+            "gl_FragData" => {
+                return Err((
+                    line,
+                    "`gl_FragData` isn't supported; write `gl_FragColor`".into(),
+                ));
+            }
+            _ => {}
+        }
+        let builtin = [
+            ("gl_Position", Stage::Vertex, Ty::Float(4)),
+            ("gl_PointSize", Stage::Vertex, Ty::Float(1)),
+            ("gl_FragColor", Stage::Fragment, Ty::Float(4)),
+            ("gl_FragCoord", Stage::Fragment, Ty::Float(4)),
+            ("gl_PointCoord", Stage::Fragment, Ty::Float(2)),
+        ]
+        .into_iter()
+        .find(|(builtin, ..)| *builtin == name);
+        let interface = self
+            .attributes
+            .iter()
+            .chain(&self.varyings)
+            .any(|i| i.name == name)
+            && !self.scopes[1..].iter().any(|s| s.contains_key(name));
+        if let Some((builtin, stage, ty)) = builtin {
+            if stage != self.stage {
+                return Err((
+                    line,
+                    format!("`{builtin}` isn't available in {} shaders", self.stage),
+                ));
+            }
+            if !self.in_main {
+                return Err((line, format!("`{builtin}` can only be used in `main`")));
+            }
+            self.builtins.insert(builtin);
+            return Ok((name.into(), ty));
+        }
+        if name.starts_with("gl_") {
+            return Err((line, format!("`{name}` isn't supported")));
+        }
+        if interface && !self.in_main {
+            return Err((
+                line,
+                format!("`{name}` can only be used in `main`, where Cg passes it as a parameter"),
+            ));
+        }
+        Ok((name.into(), self.lookup(name)))
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], line: u32) -> Result<(String, Ty), Error> {
+        let mut strings = Vec::new();
+        let mut tys = Vec::new();
+        for arg in args {
+            let (s, ty) = self.expr(arg)?;
+            strings.push(s);
+            tys.push(ty);
+        }
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err((line, format!("`{name}` takes {n} arguments")))
+            }
+        };
+        let joined = strings.join(", ");
+
+        if super::ast::BUILTIN_TYPES.contains(&name) {
+            return self.constructor(name, strings, tys, line);
+        }
+        if self.structs.contains_key(name) {
+            return Err((
+                line,
+                format!(
+                    "struct constructors aren't supported by Cg; set the members of `{name}` one by one"
+                ),
+            ));
+        }
+        if let Some((_, op)) = COMPARISONS.iter().find(|(glsl, _)| *glsl == name) {
+            arity(2)?;
+            let ty = Ty::Bool(tys[0].width());
+            return Ok((format!("({} {op} {})", strings[0], strings[1]), ty));
+        }
+        Ok(match name {
+            "texture2D" | "texture2DProj" | "textureCube" if args.len() == 3 => {
+                return Err((line, format!("`{name}` with a bias isn't supported")));
+            }
+            "texture2D" | "texture2DProj" | "textureCube" => {
+                arity(2)?;
+                let cg = RENAMED.iter().find(|(glsl, _)| *glsl == name).unwrap().1;
+                (format!("{cg}({joined})"), Ty::Float(4))
+            }
+            "texture2DLod" => {
+                arity(3)?;
+                let [s, uv, lod] = &strings[..] else {
+                    unreachable!()
+                };
+                (
+                    format!("tex2Dlod({s}, float4({uv}, 0.0, {lod}))"),
+                    Ty::Float(4),
+                )
+            }
+            "textureCubeLod" => {
+                arity(3)?;
+                let [s, dir, lod] = &strings[..] else {
+                    unreachable!()
+                };
+                (
+                    format!("texCUBElod({s}, float4({dir}, {lod}))"),
+                    Ty::Float(4),
+                )
+            }
+            "texture2DProjLod" => {
+                return Err((line, format!("`{name}` isn't supported")));
+            }
+            // `x - y * floor(x / y)`, rearranged so only `y` is repeated. Cg's `fmod`
+            // truncates instead, so it differs for negative operands.
+            "mod" => {
+                arity(2)?;
+                let (x, y) = (atom(&strings[0]), atom(&strings[1]));
+                if y != strings[1] {
+                    return Err((
+                        line,
+                        "`mod` needs a name or number as its divisor; assign it to a variable first"
+                            .into(),
+                    ));
+                }
+                (format!("({y} * frac({x} / {y}))"), widest(&tys))
+            }
+            "atan" if args.len() == 2 => (format!("atan2({joined})"), widest(&tys)),
+            "matrixCompMult" => {
+                arity(2)?;
+                (
+                    format!("({} * {})", atom(&strings[0]), atom(&strings[1])),
+                    tys[0].clone(),
+                )
+            }
+            "not" => {
+                arity(1)?;
+                (format!("(!{})", atom(&strings[0])), tys[0].clone())
+            }
+            "any" | "all" => (format!("{name}({joined})"), Ty::Bool(1)),
+            "length" | "distance" | "dot" => (format!("{name}({joined})"), Ty::Float(1)),
+            "cross" => (format!("cross({joined})"), Ty::Float(3)),
+            _ => {
+                if let Some((_, cg)) = RENAMED.iter().find(|(glsl, _)| *glsl == name) {
+                    (format!("{cg}({joined})"), widest(&tys))
+                } else if SAME.contains(&name) {
+                    (format!("{name}({joined})"), widest(&tys))
+                } else if let Some(ty) = self.functions.get(name) {
+                    (format!("{name}({joined})"), ty.clone())
+                } else {
+                    return Err((line, format!("unknown function `{name}`")));
+                }
+            }
+        })
+    }
+
+    fn constructor(
+        &self,
+        name: &str,
+        args: Vec<String>,
+        tys: Vec<Ty>,
+        line: u32,
+    ) -> Result<(String, Ty), Error> {
+        let ty = Ty::from_glsl(name);
+        let cg = cg_type(name);
+        if matches!(ty, Ty::Sampler | Ty::Void) || args.is_empty() {
+            return Err((line, format!("`{name}` can't be constructed like that")));
+        }
+        if args.len() > 1 {
+            return Ok((format!("{cg}({})", args.join(", ")), ty));
+        }
+        let arg = &args[0];
+        let s = match (&ty, &tys[0]) {
+            // A scalar fills the diagonal.
+            (Ty::Mat(n), arg_ty) if arg_ty.is_scalar() => {
+                let n = *n as usize;
+                let arg = atom(arg);
+                let cells: Vec<&str> = (0..n * n)
+                    .map(|i| {
+                        if i % (n + 1) == 0 {
+                            arg.as_str()
+                        } else {
+                            "0.0"
+                        }
+                    })
+                    .collect();
+                format!("{cg}({})", cells.join(", "))
+            }
+            (Ty::Mat(n), Ty::Mat(m)) if n > m => {
+                return Err((
+                    line,
+                    format!("`{name}` from a smaller matrix isn't supported"),
+                ));
+            }
+            _ => format!("(({cg}){})", atom(arg)),
+        };
+        Ok((s, ty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Stage, translate, translate_pair};
+
+    fn vertex(body: &str) -> String {
+        translate(
+            &format!("uniform mat3 m;\nvoid main() {{\n{body}\n}}"),
+            Stage::Vertex,
+        )
+        .unwrap()
+    }
+
+    fn error(source: &str, stage: Stage) -> String {
+        translate(source, stage).unwrap_err().message
+    }
+
+    /// The error from `body` as the vertex `main`.
+    fn main_error(body: &str) -> String {
+        error(
+            &format!("uniform mat3 m;\nvoid main() {{ {body} }}"),
+            Stage::Vertex,
+        )
+    }
+
+    #[test]
+    fn matrix_operands_swap() {
+        let cg = vertex(
+            "vec3 v = m * vec3(1.0);
+            vec3 w = v * m;
+            mat3 n = m * m;
+            vec3 s = 2.0 * v;
+            mat3 k = m * 2.0;
+            v *= m;
+            n *= m;
+            w = (m * v).xyz * m;",
+        );
+        for line in [
+            "float3 v = mul(((float3)1.0), m);",
+            "float3 w = mul(m, v);",
+            "float3x3 n = mul(m, m);",
+            "float3 s = 2.0 * v;",
+            "float3x3 k = m * 2.0;",
+            "v = mul(m, v);",
+            "n = mul(m, n);",
+            "w = mul(m, (mul(v, m)).xyz);",
+        ] {
+            assert!(cg.contains(line), "no `{line}` in\n{cg}");
+        }
+    }
+
+    #[test]
+    fn swizzles_keep_their_width() {
+        let cg = vertex(
+            "vec4 v = vec4(1.0);
+            vec3 a = m * v.xyz;
+            float b = v.x * 2.0;
+            vec3 c = m[1] * m;",
+        );
+        assert!(cg.contains("float3 a = mul(v.xyz, m);"), "{cg}");
+        // A scalar times a matrix is component-wise, not a product.
+        assert!(cg.contains("float b = v.x * 2.0;"), "{cg}");
+        assert!(cg.contains("float3 c = mul(m, m[1]);"), "{cg}");
+    }
+
+    #[test]
+    fn varyings_pair_by_name() {
+        let (vertex, fragment) = translate_pair(
+            "attribute vec3 aPos;
+            varying vec2 vUv;
+            varying vec4 vColor;
+            void main() { vUv = aPos.xy; vColor = vec4(1.0); gl_Position = vec4(aPos, 1.0); }",
+            "varying vec4 vColor;
+            varying vec2 vUv;
+            void main() { gl_FragColor = vColor * vUv.x; }",
+        )
+        .unwrap();
+        assert!(vertex.contains("float3 aPos,\n"), "{vertex}");
+        assert!(vertex.contains("float2 out vUv : TEXCOORD0"), "{vertex}");
+        assert!(vertex.contains("float4 out vColor : TEXCOORD1"), "{vertex}");
+        assert!(fragment.contains("float4 vColor : TEXCOORD1"), "{fragment}");
+        assert!(fragment.contains("float2 vUv : TEXCOORD0"), "{fragment}");
+        // Alone, the fragment shader can only number them in its own order.
+        let alone = translate(
+            "varying vec4 vColor; void main() { gl_FragColor = vColor; }",
+            Stage::Fragment,
+        )
+        .unwrap();
+        assert!(alone.contains("float4 vColor : TEXCOORD0"), "{alone}");
+    }
+
+    #[test]
+    fn builtins() {
+        let cg = vertex(
+            "float a = mix(0.0, 1.0, fract(0.5)) + inversesqrt(4.0);
+            bvec2 b = lessThan(vec2(0.0), vec2(1.0));
+            bool c = any(not(b)) ^^ true;
+            float d = atan(1.0, 2.0);
+            gl_PointSize = 2.0;
+            gl_Position = vec4(mat2(1.0)[0], 0.0, 1.0);",
+        );
+        for line in [
+            "float a = lerp(0.0, 1.0, frac(0.5)) + rsqrt(4.0);",
+            "bool2 b = (((float2)0.0) < ((float2)1.0));",
+            "bool c = (any((!b)) != true);",
+            "float d = atan2(1.0, 2.0);",
+            "float out gl_PointSize : PSIZE",
+            "gl_Position = float4(float2x2(1.0, 0.0, 0.0, 1.0)[0], 0.0, 1.0);",
+        ] {
+            assert!(cg.contains(line), "no `{line}` in\n{cg}");
+        }
+        let cg = translate(
+            "uniform sampler2D tex;
+            uniform samplerCube sky;
+            void main() {
+                gl_FragColor = texture2DLod(tex, gl_PointCoord, 1.0)
+                    + textureCube(sky, gl_FragCoord.xyz)
+                    + textureCubeLod(sky, vec3(0.0), 2.0);
+            }",
+            Stage::Fragment,
+        )
+        .unwrap();
+        for line in [
+            "uniform samplerCUBE sky;",
+            "tex2Dlod(tex, float4(gl_PointCoord, 0.0, 1.0))",
+            "texCUBE(sky, gl_FragCoord.xyz)",
+            "texCUBElod(sky, float4(((float3)0.0), 2.0))",
+            "float4 gl_FragCoord : WPOS",
+            "float2 gl_PointCoord : SPRITECOORD",
+        ] {
+            assert!(cg.contains(line), "no `{line}` in\n{cg}");
+        }
+    }
+
+    #[test]
+    fn mod_evaluates_dividend_once() {
+        let cg = vertex("vec3 v = mod(m * vec3(1.0), 2.0); float f = mod(v.x, v.y);");
+        assert!(
+            cg.contains("float3 v = (2.0 * frac((mul(((float3)1.0), m)) / 2.0));"),
+            "{cg}"
+        );
+        assert!(cg.contains("float f = (v.y * frac(v.x / v.y));"), "{cg}");
+        assert_eq!(
+            main_error("float f = mod(1.0, m[0].x + 1.0);"),
+            "`mod` needs a name or number as its divisor; assign it to a variable first"
+        );
+    }
+
+    #[test]
+    fn directives_and_statements() {
+        let cg = translate(
+            "#version 100
+            #define TINT vec4(0.5)
+            void main() {
+                for (int i = 0; i < 2; i++) if (i == 1) discard; else continue;
+            }",
+            Stage::Fragment,
+        )
+        .unwrap();
+        assert!(!cg.contains("version"), "{cg}");
+        assert!(cg.contains("#define TINT float4(0.5)"), "{cg}");
+        assert!(
+            cg.contains(
+                "    for (int i = 0; i < 2; i++) {\n        if (i == 1) {\n            discard;\n        } else {\n            continue;\n        }\n    }\n"
+            ),
+            "{cg}"
+        );
+    }
+
+    #[test]
+    fn global_errors() {
+        let fragment = |source| error(source, Stage::Fragment);
+        let vertex = |source| error(source, Stage::Vertex);
+        assert_eq!(
+            fragment("attribute vec2 a; void main() {}"),
+            "attributes are only allowed in vertex shaders"
+        );
+        assert_eq!(
+            vertex("attribute mat2 a; void main() {}"),
+            "attribute matrices aren't supported"
+        );
+        assert_eq!(
+            vertex("varying vec2 v[2]; void main() {}"),
+            "varying arrays aren't supported"
+        );
+        assert_eq!(vertex("void f() {}"), "the shader has no `main`");
+        assert_eq!(
+            vertex("float main() { return 1.0; }"),
+            "`main` must be `void main()`"
+        );
+        assert_eq!(
+            vertex("uniform float half; void main() {}"),
+            "`half` is a reserved word in Cg"
+        );
+        assert_eq!(
+            vertex("void float2() {} void main() {}"),
+            "`float2` is a reserved word in Cg"
+        );
+        let varyings: String = (0..11).map(|i| format!("varying float v{i};")).collect();
+        assert_eq!(
+            vertex(&format!("{varyings} void main() {{}}")),
+            "Cg allows at most 10 varyings"
+        );
+        let error = translate_pair(
+            "void main() { gl_Position = vec4(0.0); }",
+            "varying vec2 vUv; void main() { gl_FragColor = vec4(vUv, 0.0, 1.0); }",
+        )
+        .unwrap_err();
+        assert_eq!(
+            (error.stage, error.message.as_str()),
+            (
+                Stage::Fragment,
+                "varying `vUv` isn't written by the vertex shader"
+            )
+        );
+    }
+
+    #[test]
+    fn builtin_errors() {
+        assert_eq!(
+            error(
+                "void main() { gl_FragData[0] = vec4(1.0); }",
+                Stage::Fragment
+            ),
+            "`gl_FragData` isn't supported; write `gl_FragColor`"
+        );
+        assert_eq!(
+            main_error("gl_FragColor = vec4(1.0);"),
+            "`gl_FragColor` isn't available in vertex shaders"
+        );
+        assert_eq!(
+            error(
+                "vec4 f() { return gl_Position; } void main() {}",
+                Stage::Vertex
+            ),
+            "`gl_Position` can only be used in `main`"
+        );
+        assert_eq!(
+            main_error("float d = gl_DepthRange.near;"),
+            "`gl_DepthRange` isn't supported"
+        );
+        assert_eq!(
+            error(
+                "varying vec2 v; vec2 f() { return v; } void main() {}",
+                Stage::Vertex
+            ),
+            "`v` can only be used in `main`, where Cg passes it as a parameter"
+        );
+    }
+
+    #[test]
+    fn call_errors() {
+        assert_eq!(
+            main_error("float f = lessThan(1.0);"),
+            "`lessThan` takes 2 arguments"
+        );
+        assert_eq!(main_error("float f = mod(1.0);"), "`mod` takes 2 arguments");
+        assert_eq!(
+            error(
+                "struct S { float x; }; void main() { S s = S(1.0); }",
+                Stage::Vertex
+            ),
+            "struct constructors aren't supported by Cg; set the members of `S` one by one"
+        );
+        assert_eq!(
+            error(
+                "uniform sampler2D t; void main() { gl_FragColor = texture2D(t, vec2(0.0), 1.0); }",
+                Stage::Fragment
+            ),
+            "`texture2D` with a bias isn't supported"
+        );
+        assert_eq!(
+            main_error("vec4 c = texture2DProjLod(t, vec3(0.0), 1.0);"),
+            "`texture2DProjLod` isn't supported"
+        );
+        assert_eq!(
+            main_error("float f = noise1(1.0);"),
+            "unknown function `noise1`"
+        );
+        assert_eq!(
+            main_error("vec2 v = vec2();"),
+            "`vec2` can't be constructed like that"
+        );
+        assert_eq!(
+            main_error("mat4 n = mat4(m);"),
+            "`mat4` from a smaller matrix isn't supported"
+        );
+    }
+
+    #[test]
+    fn error_lines() {
+        let error = translate(
+            "void main() {\n    gl_Position = vec4(0.0);\n    foo();\n}",
+            Stage::Vertex,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "vertex shader line 3: unknown function `foo`"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Translation of a practical GLSL ES 1.00 subset into the Cg vitaGL compiles.
+//!
+//! Attributes become parameters of the vertex `main`, varyings become `TEXCOORDn`
+//! parameters of both, and `gl_Position`, `gl_PointSize`, `gl_FragColor`, `gl_FragCoord`
+//! and `gl_PointCoord` become parameters with the matching semantics. Types and built-ins
+//! are renamed, and matrix products are rewritten to `mul` with the operands swapped, since
+//! Cg reads a GLSL matrix as its transpose. Anything without a faithful Cg equivalent is
+//! an error rather than a guess.
+//!
+//! The crate has no dependencies, so build scripts can translate shaders as easily as a
+//! program can at runtime.
+//!
+//! ```rust
+//! use vita_gl_helpers_cg::glsl::translate_pair;
+//! let (vertex, fragment) = translate_pair(
+//!     "
+//!     attribute vec2 aPos;
+//!     uniform mat4 mvp;
+//!     varying vec2 vUv;
+//!     void main() {
+//!         vUv = aPos * 0.5 + 0.5;
+//!         gl_Position = mvp * vec4(aPos, 0.0, 1.0);
+//!     }
+//!     ",
+//!     "
+//!     precision mediump float;
+//!     uniform sampler2D tex;
+//!     varying vec2 vUv;
+//!     void main() {
+//!         gl_FragColor = mix(texture2D(tex, vUv), vec4(1.0), 0.25);
+//!     }
+//!     ",
+//! )
+//! .unwrap();
+//! assert!(vertex.contains("gl_Position = mul(float4(aPos, 0.0, 1.0), mvp);"));
+//! assert!(vertex.contains("float2 out vUv : TEXCOORD0"));
+//! assert!(fragment.contains("lerp(tex2D(tex, vUv), ((float4)1.0), 0.25)"));
+//! assert!(fragment.contains("float4 out gl_FragColor : COLOR"));
+//! ```
+
+use crate::lexer;
+
+mod ast;
+mod emit;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Vertex,
+    Fragment,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::Vertex => "vertex",
+            Stage::Fragment => "fragment",
+        })
+    }
+}
+
+/// Translates one shader. Varyings get `TEXCOORD` semantics in declaration order, so a
+/// pair translated separately only matches if both declare their varyings in the same
+/// order; [`translate_pair`] doesn't have that restriction.
+pub fn translate(source: &str, stage: Stage) -> Result<String, TranslateError> {
+    translate_inner(source, stage, None).map(|(cg, _)| cg)
+}
+
+/// Translates a vertex and fragment shader, giving each varying the semantic the vertex
+/// shader assigned it.
+pub fn translate_pair(vertex: &str, fragment: &str) -> Result<(String, String), TranslateError> {
+    let (vertex, varyings) = translate_inner(vertex, Stage::Vertex, None)?;
+    let (fragment, _) = translate_inner(fragment, Stage::Fragment, Some(&varyings))?;
+    Ok((vertex, fragment))
+}
+
+fn translate_inner(
+    source: &str,
+    stage: Stage,
+    known_varyings: Option<&[String]>,
+) -> Result<(String, Vec<String>), TranslateError> {
+    let error = |(line, message)| TranslateError {
+        stage,
+        line,
+        message,
+    };
+    let tokens = lexer::tokenize(source).map_err(|e| error((e.line, e.message)))?;
+    let items = ast::Parser::new(tokens).items().map_err(error)?;
+    emit::Emitter::new(stage, known_varyings)
+        .emit(&items)
+        .map_err(error)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslateError {
+    pub stage: Stage,
+    /// The line in the GLSL source.
+    pub line: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} shader line {}: {}",
+            self.stage, self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for TranslateError {}
@@ -0,0 +1,170 @@
+use crate::ParseError;
+
+/// Operators longer than one character. Everything else is a [`TokenKind::Punct`].
+const OPS: &[&str] = &[
+    "<<=", ">>=", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "==", "!=", "<=",
+    ">=", "&&", "||", "^^", "<<", ">>",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+    Op(&'static str),
+    /// A whole preprocessor line without the `#`, continuation lines joined.
+    Directive(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: u32,
+}
+
+impl Token {
+    pub fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+    pub fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            TokenKind::Ident(ident) => Some(ident),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            TokenKind::Ident(s) | TokenKind::Number(s) => write!(f, "{s}"),
+            TokenKind::Str(s) => write!(f, "\"{s}\""),
+            TokenKind::Punct(c) => write!(f, "{c}"),
+            TokenKind::Op(op) => write!(f, "{op}"),
+            TokenKind::Directive(d) => write!(f, "#{d}"),
+        }
+    }
+}
+
+/// The tokens of `source`. Preprocessor lines become single [`TokenKind::Directive`]s;
+/// nothing is expanded.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut at_line_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            at_line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' && at_line_start {
+            let start_line = line;
+            let mut directive = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != '\n' {
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    line += 1;
+                    i += 2;
+                    directive.push(' ');
+                    continue;
+                }
+                directive.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Directive(directive.trim().to_string()),
+                line: start_line,
+            });
+            continue;
+        }
+        at_line_start = false;
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            let start_line = line;
+            i += 2;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ParseError::new(start_line, "unterminated comment")),
+                    Some('*') if chars.get(i + 1) == Some(&'/') => break,
+                    Some('\n') => line += 1,
+                    _ => {}
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+        let start = i;
+        let kind = if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let hex = chars[i..].starts_with(&['0', 'x']) || chars[i..].starts_with(&['0', 'X']);
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                let exponent = !hex && matches!(chars[i], 'e' | 'E');
+                i += 1;
+                if exponent && matches!(chars.get(i), Some('+' | '-')) {
+                    i += 1;
+                }
+            }
+            TokenKind::Number(chars[start..i].iter().collect())
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' && chars[i] != '\n' {
+                i += 1;
+            }
+            if chars.get(i) != Some(&'"') {
+                return Err(ParseError::new(line, "unterminated string"));
+            }
+            i += 1;
+            TokenKind::Str(chars[start + 1..i - 1].iter().collect())
+        } else if let Some(op) = OPS
+            .iter()
+            .find(|op| op.chars().eq(chars[i..].iter().take(op.len()).copied()))
+        {
+            i += op.len();
+            TokenKind::Op(op)
+        } else {
+            i += 1;
+            TokenKind::Punct(c)
+        };
+        tokens.push(Token { kind, line });
+    }
+    Ok(tokens)
+}
+
+/// `define NAME value`, but not `define NAME(args) value`.
+pub fn object_define(directive: &str) -> Option<(String, String)> {
+    let rest = directive.trim_start().strip_prefix("define")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let name_len = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    let (name, value) = rest.split_at(name_len);
+    if name.is_empty() || value.starts_with('(') {
+        return None;
+    }
+    Some((name.to_string(), value.trim().to_string()))
+}
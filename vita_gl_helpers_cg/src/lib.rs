@@ -0,0 +1,244 @@
+//! A host-side reader for the Cg dialect vitaGL compiles, so shader interfaces
+//! can be checked without a console.
+//!
+//! ```rust
+//! use vita_gl_helpers_cg::{CgProgram, CgType, Kind, Scalar};
+//! let program = CgProgram::parse(
+//!     "
+//!     uniform float4x4 mvp;
+//!     void main(float3 aPos, float4 aColor : COLOR0, float4 out vColor : COLOR,
+//!               float4 out gl_Position : POSITION) {
+//!         gl_Position = mul(mvp, float4(aPos, 1.0));
+//!         vColor = aColor;
+//!     }
+//!     ",
+//! )
+//! .unwrap();
+//! let mvp = program.variable("mvp").unwrap();
+//! assert_eq!(mvp.kind, Kind::Uniform);
+//! assert_eq!(mvp.type_, CgType::Matrix(Scalar::Float, 4, 4));
+//! let inputs: Vec<_> = program.inputs().map(|v| v.name.as_str()).collect();
+//! assert_eq!(inputs, ["aPos", "aColor"]);
+//! assert_eq!(program.variable("vColor").unwrap().semantic.as_deref(), Some("COLOR"));
+//! ```
+
+pub mod glsl;
+mod lexer;
+mod parser;
+
+/// The element type of a scalar, vector or matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scalar {
+    Float,
+    Half,
+    Fixed,
+    Int,
+    Bool,
+}
+
+impl Scalar {
+    fn from_prefix(name: &str) -> Option<(Scalar, &str)> {
+        [
+            ("float", Scalar::Float),
+            ("half", Scalar::Half),
+            ("fixed", Scalar::Fixed),
+            ("int", Scalar::Int),
+            ("bool", Scalar::Bool),
+        ]
+        .into_iter()
+        .find_map(|(prefix, scalar)| Some((scalar, name.strip_prefix(prefix)?)))
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scalar::Float => "float",
+            Scalar::Half => "half",
+            Scalar::Fixed => "fixed",
+            Scalar::Int => "int",
+            Scalar::Bool => "bool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CgType {
+    Scalar(Scalar),
+    /// e.g. `float3` is `Vector(Float, 3)`.
+    Vector(Scalar, u8),
+    /// e.g. `float3x4` is `Matrix(Float, 3, 4)`, three rows of four.
+    Matrix(Scalar, u8, u8),
+    Sampler2D,
+    SamplerCube,
+    /// Structs, other samplers and anything else, by name.
+    Other(String),
+}
+
+impl CgType {
+    pub fn parse(name: &str) -> CgType {
+        let dim = |d: &str| d.parse::<u8>().ok().filter(|d| (1..=4).contains(d));
+        match name {
+            "sampler2D" => return CgType::Sampler2D,
+            "samplerCUBE" => return CgType::SamplerCube,
+            _ => {}
+        }
+        let Some((scalar, rest)) = Scalar::from_prefix(name) else {
+            return CgType::Other(name.to_string());
+        };
+        if rest.is_empty() {
+            return CgType::Scalar(scalar);
+        }
+        if let Some(n) = dim(rest) {
+            return CgType::Vector(scalar, n);
+        }
+        match rest.split_once('x').map(|(r, c)| (dim(r), dim(c))) {
+            Some((Some(rows), Some(columns))) => CgType::Matrix(scalar, rows, columns),
+            _ => CgType::Other(name.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for CgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CgType::Scalar(s) => write!(f, "{}", s.name()),
+            CgType::Vector(s, n) => write!(f, "{}{n}", s.name()),
+            CgType::Matrix(s, rows, columns) => write!(f, "{}{rows}x{columns}", s.name()),
+            CgType::Sampler2D => write!(f, "sampler2D"),
+            CgType::SamplerCube => write!(f, "samplerCUBE"),
+            CgType::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// How a variable gets its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// `uniform`, or a global without `static`, `const`, `in` or `out`.
+    Uniform,
+    /// An entry point parameter without `out`, or an `in` global.
+    /// In a vertex shader these are the attributes.
+    In,
+    Out,
+    InOut,
+}
+
+/// A uniform, input or output of a shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgVariable {
+    pub name: String,
+    pub type_: CgType,
+    pub array_len: Option<u32>,
+    /// e.g. `POSITION` or `COLOR0`, as written.
+    pub semantic: Option<String>,
+    pub kind: Kind,
+    /// Where it was declared.
+    pub line: u32,
+}
+
+impl CgVariable {
+    /// Whether the semantic is one the GPU fills in itself, like `INDEX` or `WPOS`.
+    /// Such inputs are never attributes, so GL doesn't report them.
+    pub fn is_system_value(&self) -> bool {
+        self.semantic.as_deref().is_some_and(|semantic| {
+            SYSTEM_SEMANTICS
+                .iter()
+                .any(|system| semantic.eq_ignore_ascii_case(system))
+        })
+    }
+}
+
+const SYSTEM_SEMANTICS: &[&str] = &[
+    "INDEX",
+    "INSTANCE",
+    "WPOS",
+    "FACE",
+    "SPRITECOORD",
+    "FRAGCOLOR",
+];
+
+impl std::fmt::Display for CgVariable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            Kind::Uniform => write!(f, "uniform ")?,
+            Kind::In => write!(f, "in ")?,
+            Kind::Out => write!(f, "out ")?,
+            Kind::InOut => write!(f, "inout ")?,
+        }
+        write!(f, "{} {}", self.type_, self.name)?;
+        if let Some(len) = self.array_len {
+            write!(f, "[{len}]")?;
+        }
+        if let Some(semantic) = &self.semantic {
+            write!(f, " : {semantic}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPoint {
+    pub name: String,
+    pub return_type: CgType,
+    pub return_semantic: Option<String>,
+}
+
+/// The interface of one Cg shader: its globals and entry point parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgProgram {
+    /// `None` if the source has no `main`.
+    pub entry_point: Option<EntryPoint>,
+    /// Globals in order, then the entry point's parameters in order.
+    pub variables: Vec<CgVariable>,
+}
+
+impl CgProgram {
+    /// Parses a shader whose entry point is `main`.
+    pub fn parse(source: &str) -> Result<CgProgram, ParseError> {
+        Self::parse_with_entry(source, "main")
+    }
+    pub fn parse_with_entry(source: &str, entry_point: &str) -> Result<CgProgram, ParseError> {
+        parser::parse(source, entry_point)
+    }
+    pub fn variable(&self, name: &str) -> Option<&CgVariable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+    pub fn uniforms(&self) -> impl Iterator<Item = &CgVariable> {
+        self.variables.iter().filter(|v| v.kind == Kind::Uniform)
+    }
+    pub fn inputs(&self) -> impl Iterator<Item = &CgVariable> {
+        self.variables
+            .iter()
+            .filter(|v| matches!(v.kind, Kind::In | Kind::InOut))
+    }
+    /// The inputs a vertex shader reads from attributes, i.e. without system values.
+    pub fn attributes(&self) -> impl Iterator<Item = &CgVariable> {
+        self.inputs().filter(|v| !v.is_system_value())
+    }
+    pub fn outputs(&self) -> impl Iterator<Item = &CgVariable> {
+        self.variables
+            .iter()
+            .filter(|v| matches!(v.kind, Kind::Out | Kind::InOut))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: u32,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: u32, message: impl Into<String>) -> Self {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
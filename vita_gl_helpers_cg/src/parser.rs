@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+
+use crate::{
+    CgProgram, CgType, CgVariable, EntryPoint, Kind, ParseError,
+    lexer::{Token, TokenKind, object_define, tokenize},
+};
+
+/// Words that combine with the type after them, as in `unsigned int`.
+const TYPE_PREFIXES: &[&str] = &["unsigned", "signed"];
+
+const QUALIFIERS: &[&str] = &[
+    "uniform",
+    "in",
+    "out",
+    "inout",
+    "const",
+    "static",
+    "extern",
+    "volatile",
+    "centroid",
+    "nointerpolation",
+];
+
+type Defines = HashMap<String, String>;
+
+pub fn parse(source: &str, entry: &str) -> Result<CgProgram, ParseError> {
+    let (directives, tokens): (Vec<Token>, Vec<Token>) = tokenize(source)?
+        .into_iter()
+        .partition(|t| matches!(t.kind, TokenKind::Directive(_)));
+    let defines: Defines = directives
+        .iter()
+        .filter_map(|t| match &t.kind {
+            TokenKind::Directive(d) => object_define(d),
+            _ => None,
+        })
+        .collect();
+    let mut variables = Vec::new();
+    let mut entry_point = None;
+    let mut params = Vec::new();
+    let mut pos = 0;
+    while pos < tokens.len() {
+        let start = pos;
+        pos = statement_end(&tokens, pos)?;
+        let decl = &tokens[start..pos];
+        let Some(end) = tokens.get(pos) else {
+            let line = decl.last().map_or(0, |t| t.line);
+            return Err(ParseError::new(line, "expected `;` at the end of the file"));
+        };
+        pos += 1;
+        if end.is_punct('{') {
+            pos = skip_block(&tokens, pos, end.line)?;
+            match decl.iter().position(|t| t.is_punct('(')) {
+                Some(open) if decl[..open].last().and_then(Token::ident) == Some(entry) => {
+                    let (point, entry_params) = parse_entry(decl, open, &defines)?;
+                    entry_point = Some(point);
+                    params = entry_params;
+                }
+                Some(_) => {}
+                // A struct, which may declare variables before its `;`.
+                None => pos = statement_end(&tokens, pos)? + 1,
+            }
+            continue;
+        }
+        if decl.is_empty() || decl[0].ident() == Some("typedef") || is_prototype(decl) {
+            continue;
+        }
+        let (qualifiers, declarators) = parse_declaration(decl, &defines)?;
+        let kind = if qualifiers.contains(&"uniform") {
+            Kind::Uniform
+        } else if qualifiers.contains(&"inout") {
+            Kind::InOut
+        } else if qualifiers.contains(&"out") {
+            Kind::Out
+        } else if qualifiers.contains(&"in") {
+            Kind::In
+        } else if qualifiers.contains(&"static") || qualifiers.contains(&"const") {
+            continue;
+        } else {
+            Kind::Uniform
+        };
+        variables.extend(declarators.into_iter().map(|d| d.into_variable(kind)));
+    }
+    variables.append(&mut params);
+    Ok(CgProgram {
+        entry_point,
+        variables,
+    })
+}
+
+/// The index of the `;` or `{` that ends the statement starting at `pos`,
+/// or `tokens.len()` if there is none.
+fn statement_end(tokens: &[Token], mut pos: usize) -> Result<usize, ParseError> {
+    let mut depth = 0;
+    while let Some(token) = tokens.get(pos) {
+        match token.kind {
+            TokenKind::Punct('(' | '[') => depth += 1,
+            TokenKind::Punct(')' | ']') => depth -= 1,
+            TokenKind::Punct(';' | '{') if depth == 0 => break,
+            TokenKind::Punct('}') if depth == 0 => {
+                return Err(ParseError::new(token.line, "unexpected `}`"));
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    Ok(pos)
+}
+
+/// Skips to just after the `}` closing a block whose `{` is just before `pos`.
+fn skip_block(tokens: &[Token], mut pos: usize, open_line: u32) -> Result<usize, ParseError> {
+    let mut depth = 1;
+    while let Some(token) = tokens.get(pos) {
+        pos += 1;
+        match token.kind {
+            TokenKind::Punct('{') => depth += 1,
+            TokenKind::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(pos);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(ParseError::new(open_line, "unclosed `{`"))
+}
+
+/// `float4 f(float4 c);`, as opposed to `float4 c = float4(1, 1, 1, 1);`
+fn is_prototype(decl: &[Token]) -> bool {
+    let paren = decl.iter().position(|t| t.is_punct('('));
+    let equals = decl.iter().position(|t| t.is_punct('='));
+    match (paren, equals) {
+        (Some(paren), Some(equals)) => paren < equals,
+        (paren, _) => paren.is_some(),
+    }
+}
+
+fn parse_entry(
+    decl: &[Token],
+    open: usize,
+    defines: &Defines,
+) -> Result<(EntryPoint, Vec<CgVariable>), ParseError> {
+    let name = decl[open - 1].ident().unwrap_or_default().to_string();
+    let return_type = decl[..open - 1]
+        .iter()
+        .rev()
+        .find_map(Token::ident)
+        .filter(|ident| !QUALIFIERS.contains(ident))
+        .ok_or_else(|| ParseError::new(decl[open].line, "expected a return type"))?;
+    let close = matching_close(decl, open)?;
+    let return_semantic = match &decl[close + 1..] {
+        [colon, semantic, ..] if colon.is_punct(':') => semantic.ident().map(str::to_string),
+        _ => None,
+    };
+    let mut params = Vec::new();
+    for param in split_commas(&decl[open + 1..close]) {
+        if param.is_empty() || (param.len() == 1 && param[0].ident() == Some("void")) {
+            continue;
+        }
+        let (qualifiers, declarators) = parse_declaration(param, defines)?;
+        let kind = if qualifiers.contains(&"uniform") {
+            Kind::Uniform
+        } else if qualifiers.contains(&"inout") {
+            Kind::InOut
+        } else if qualifiers.contains(&"out") {
+            Kind::Out
+        } else {
+            Kind::In
+        };
+        params.extend(declarators.into_iter().map(|d| d.into_variable(kind)));
+    }
+    let entry_point = EntryPoint {
+        name,
+        return_type: CgType::parse(return_type),
+        return_semantic,
+    };
+    Ok((entry_point, params))
+}
+
+fn matching_close(tokens: &[Token], open: usize) -> Result<usize, ParseError> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::Punct('(') => depth += 1,
+            TokenKind::Punct(')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(ParseError::new(tokens[open].line, "unclosed `(`"))
+}
+
+/// Splits on commas outside any brackets.
+fn split_commas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Punct('(' | '[' | '{') => depth += 1,
+            TokenKind::Punct(')' | ']' | '}') => depth -= 1,
+            TokenKind::Punct(',') if depth == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&tokens[start..]);
+    parts
+}
+
+struct Declarator {
+    name: String,
+    type_: CgType,
+    array_len: Option<u32>,
+    semantic: Option<String>,
+    line: u32,
+}
+
+impl Declarator {
+    fn into_variable(self, kind: Kind) -> CgVariable {
+        CgVariable {
+            name: self.name,
+            type_: self.type_,
+            array_len: self.array_len,
+            semantic: self.semantic,
+            kind,
+            line: self.line,
+        }
+    }
+}
+
+/// `[qualifiers] type name[len] : SEMANTIC = init, name2 ...`
+fn parse_declaration<'a>(
+    decl: &'a [Token],
+    defines: &Defines,
+) -> Result<(Vec<&'a str>, Vec<Declarator>), ParseError> {
+    let line = decl[0].line;
+    let mut qualifiers = Vec::new();
+    let mut take_qualifiers = |mut i: usize| {
+        while let Some(qualifier) = decl
+            .get(i)
+            .and_then(Token::ident)
+            .filter(|ident| QUALIFIERS.contains(ident))
+        {
+            qualifiers.push(qualifier);
+            i += 1;
+        }
+        i
+    };
+    let mut i = take_qualifiers(0);
+    let mut type_ = decl
+        .get(i)
+        .and_then(Token::ident)
+        .ok_or_else(|| ParseError::new(line, "expected a type"))?
+        .to_string();
+    // `unsigned int x`, but not `unsigned x` or `unsigned in x`.
+    if TYPE_PREFIXES.contains(&type_.as_str()) {
+        let next = decl
+            .get(i + 1)
+            .and_then(Token::ident)
+            .filter(|ident| !QUALIFIERS.contains(ident));
+        if let (Some(next), Some(_)) = (next, decl.get(i + 2).and_then(Token::ident)) {
+            type_ = format!("{type_} {next}");
+            i += 1;
+        }
+    }
+    let type_ = CgType::parse(&type_);
+    // vitaGL also takes them after the type, as in `float4 out color : COLOR`.
+    let i = take_qualifiers(i + 1);
+    let mut declarators = Vec::new();
+    for part in split_commas(&decl[i..]) {
+        let Some(name) = part.first() else {
+            return Err(ParseError::new(line, "expected a name"));
+        };
+        let line = name.line;
+        let name = name
+            .ident()
+            .ok_or_else(|| ParseError::new(line, format!("expected a name, found `{name}`")))?;
+        let mut array_len = None;
+        let mut semantic = None;
+        let mut j = 1;
+        while let Some(token) = part.get(j) {
+            match token.kind {
+                TokenKind::Punct('[') => {
+                    let close = part[j..]
+                        .iter()
+                        .position(|t| t.is_punct(']'))
+                        .ok_or_else(|| ParseError::new(line, "unclosed `[`"))?;
+                    array_len = Some(parse_array_len(&part[j + 1..j + close], defines, line)?);
+                    j += close + 1;
+                }
+                TokenKind::Punct(':') => {
+                    semantic = part.get(j + 1).and_then(Token::ident).map(str::to_string);
+                    if semantic.is_none() {
+                        return Err(ParseError::new(line, "expected a semantic after `:`"));
+                    }
+                    // Anything after the semantic, like the `[0]` of `BUFFER[0]`, is skipped.
+                    break;
+                }
+                // So is the initialiser.
+                _ => break,
+            }
+        }
+        declarators.push(Declarator {
+            name: name.to_string(),
+            type_: type_.clone(),
+            array_len,
+            semantic,
+            line,
+        });
+    }
+    Ok((qualifiers, declarators))
+}
+
+fn parse_array_len(tokens: &[Token], defines: &Defines, line: u32) -> Result<u32, ParseError> {
+    let text = match tokens {
+        [token] => match &token.kind {
+            TokenKind::Number(n) => Some(n.as_str()),
+            TokenKind::Ident(name) => defines.get(name).map(String::as_str),
+            _ => None,
+        },
+        _ => None,
+    };
+    text.and_then(|t| t.trim_end_matches(['u', 'U']).parse().ok())
+        .ok_or_else(|| {
+            let written: Vec<String> = tokens.iter().map(Token::to_string).collect();
+            ParseError::new(
+                line,
+                format!("array length `{}` isn't a number", written.join(" ")),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CgProgram, CgType, Kind, Scalar};
+
+    #[test]
+    fn instanced_colorful_grid_vertex_shader() {
+        let source = include_str!("../../examples/instanced_colorful_grid/src/vert.cg");
+        let program = CgProgram::parse(source).unwrap();
+        let index = program.variable("gl_VertexIndex").unwrap();
+        assert_eq!(index.type_, CgType::Other("unsigned int".to_string()));
+        assert_eq!(index.kind, Kind::In);
+        assert_eq!(index.semantic.as_deref(), Some("INDEX"));
+        assert!(program.variable("int").is_none());
+        let uniforms: Vec<_> = program.uniforms().map(|v| v.name.as_str()).collect();
+        assert_eq!(uniforms, ["rect_dim"]);
+        let inputs: Vec<_> = program.inputs().map(|v| v.name.as_str()).collect();
+        assert_eq!(
+            inputs,
+            ["pos", "color_top", "color_bottom", "gl_VertexIndex"]
+        );
+        let attributes: Vec<_> = program.attributes().map(|v| v.name.as_str()).collect();
+        assert_eq!(attributes, ["pos", "color_top", "color_bottom"]);
+        let position = program.variable("gl_Position").unwrap();
+        assert_eq!(position.kind, Kind::Out);
+        assert_eq!(position.type_, CgType::Vector(Scalar::Float, 4));
+    }
+
+    #[test]
+    fn unsigned_alone_is_a_type() {
+        let program = CgProgram::parse("unsigned in count : INDEX;\nunsigned limit;").unwrap();
+        let count = program.variable("count").unwrap();
+        assert_eq!(count.type_, CgType::Other("unsigned".to_string()));
+        assert_eq!(count.kind, Kind::In);
+        assert_eq!(count.semantic.as_deref(), Some("INDEX"));
+        assert_eq!(program.variable("limit").unwrap().kind, Kind::Uniform);
+    }
+}
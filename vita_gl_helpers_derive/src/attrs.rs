@@ -0,0 +1,75 @@
+use proc_macro2::Span;
+use syn::{Attribute, Expr, Field, LitStr, spanned::Spanned};
+
+/// The contents of every `#[gl(...)]` attribute on a field.
+#[derive(Default)]
+pub struct GlFieldAttrs {
+    pub name: Option<LitStr>,
+    pub normalized: bool,
+    pub format: Option<Expr>,
+    pub skip: bool,
+    pub optional: bool,
+    pub len: Option<syn::LitInt>,
+    /// Every key that was given, for [`GlFieldAttrs::allow_only`].
+    seen: Vec<(&'static str, Span)>,
+}
+
+impl GlFieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = GlFieldAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("gl")) {
+            attr.parse_nested_meta(|meta| {
+                let key = if meta.path.is_ident("name") {
+                    out.name = Some(meta.value()?.parse()?);
+                    "name"
+                } else if meta.path.is_ident("normalized") {
+                    out.normalized = true;
+                    "normalized"
+                } else if meta.path.is_ident("format") {
+                    out.format = Some(meta.value()?.parse()?);
+                    "format"
+                } else if meta.path.is_ident("skip") {
+                    out.skip = true;
+                    "skip"
+                } else if meta.path.is_ident("len") {
+                    out.len = Some(meta.value()?.parse()?);
+                    "len"
+                } else if meta.path.is_ident("optional") {
+                    out.optional = true;
+                    "optional"
+                } else {
+                    return Err(meta.error("unknown `gl` attribute"));
+                };
+                out.seen.push((key, meta.path.span()));
+                Ok(())
+            })?;
+        }
+        Ok(out)
+    }
+
+    /// Rejects keys that mean nothing to the derive being expanded.
+    pub fn allow_only(&self, derive: &str, allowed: &[&str]) -> syn::Result<()> {
+        match self.seen.iter().find(|(key, _)| !allowed.contains(key)) {
+            Some((key, span)) => Err(syn::Error::new(
+                *span,
+                format!("`{key}` is not supported by derive({derive})"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The name GL knows this field by: the `name` attribute, or else the field's own name.
+    pub fn gl_name(&self, field: &Field) -> syn::Result<LitStr> {
+        match (&self.name, &field.ident) {
+            (Some(name), _) => Ok(name.clone()),
+            (None, Some(ident)) => Ok(LitStr::new(
+                ident.to_string().trim_start_matches("r#"),
+                ident.span(),
+            )),
+            (None, None) => Err(syn::Error::new(
+                field.span(),
+                "tuple fields need #[gl(name = \"...\")]",
+            )),
+        }
+    }
+}
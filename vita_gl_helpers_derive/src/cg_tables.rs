@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    Ident, LitStr, Token,
+    parse::{Parse, ParseStream},
+};
+use vita_gl_helpers_cg::{CgProgram, CgType, CgVariable, Scalar};
+
+/// `"vert.cg", "frag.cg" [, attributes = Name] [, uniforms = Name]`
+pub struct CgTablesInput {
+    vertex: LitStr,
+    fragment: LitStr,
+    attributes: Ident,
+    uniforms: Ident,
+}
+
+impl Parse for CgTablesInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vertex = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let fragment = input.parse()?;
+        let mut attributes = Ident::new("Attributes", Span::call_site());
+        let mut uniforms = Ident::new("Uniforms", Span::call_site());
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "attributes" => attributes = input.parse()?,
+                "uniforms" => uniforms = input.parse()?,
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `attributes` or `uniforms`",
+                    ));
+                }
+            }
+        }
+        Ok(CgTablesInput {
+            vertex,
+            fragment,
+            attributes,
+            uniforms,
+        })
+    }
+}
+
+pub fn expand(input: CgTablesInput) -> syn::Result<TokenStream> {
+    let (vertex_path, vertex) = load(&input.vertex)?;
+    let (fragment_path, fragment) = load(&input.fragment)?;
+
+    let attributes = vertex
+        .attributes()
+        .map(|v| {
+            let field = field_ident(&v.name, &input.vertex)?;
+            let name = &v.name;
+            Ok(quote! {
+                #[gl(name = #name)]
+                pub #field: ::vita_gl_helpers::attribute::Attribute
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let mut uniforms: Vec<(&CgVariable, &LitStr)> = Vec::new();
+    for (program, lit) in [(&vertex, &input.vertex), (&fragment, &input.fragment)] {
+        for uniform in program.uniforms() {
+            match uniforms.iter().find(|(u, _)| u.name == uniform.name) {
+                Some((earlier, _))
+                    if earlier.type_ != uniform.type_ || earlier.array_len != uniform.array_len =>
+                {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        format!(
+                            "uniform `{}` is declared as `{}` in the vertex shader but `{}` here",
+                            uniform.name, earlier, uniform
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => uniforms.push((uniform, lit)),
+            }
+        }
+    }
+    let uniforms = uniforms
+        .into_iter()
+        .map(|(v, lit)| {
+            let field = field_ident(&v.name, lit)?;
+            let name = &v.name;
+            let setter = setter(&v.type_).ok_or_else(|| {
+                syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "uniform `{}` has type `{}`, which no uniform setter takes",
+                        v.name, v.type_
+                    ),
+                )
+            })?;
+            let setter = format_ident!("{setter}");
+            let len = v.array_len.map(|len| {
+                let len = len as i32;
+                quote! { , len = #len }
+            });
+            Ok(quote! {
+                #[gl(name = #name #len)]
+                pub #field: ::vita_gl_helpers::uniforms::#setter
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let (attributes_name, uniforms_name) = (&input.attributes, &input.uniforms);
+    let (vertex_path, fragment_path) = (
+        vertex_path.to_string_lossy(),
+        fragment_path.to_string_lossy(),
+    );
+    Ok(quote! {
+        // Makes cargo rebuild when the shaders change.
+        const _: &str = ::core::include_str!(#vertex_path);
+        const _: &str = ::core::include_str!(#fragment_path);
+
+        #[derive(::vita_gl_helpers::attribute::AttributeTable, Clone, Copy, PartialEq, Eq)]
+        #[allow(non_snake_case)]
+        pub struct #attributes_name {
+            #(#attributes),*
+        }
+
+        #[derive(::vita_gl_helpers::uniforms::UniformTable, Clone, Copy, PartialEq, Eq)]
+        #[allow(non_snake_case)]
+        pub struct #uniforms_name {
+            #(#uniforms),*
+        }
+    })
+}
+
+/// Reads and parses a shader, relative to the manifest of the crate being built.
+fn load(path: &LitStr) -> syn::Result<(PathBuf, CgProgram)> {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let full_path = PathBuf::from(manifest_dir).join(path.value());
+    let source = std::fs::read_to_string(&full_path).map_err(|e| {
+        syn::Error::new(
+            path.span(),
+            format!("couldn't read {}: {e}", full_path.display()),
+        )
+    })?;
+    let program = CgProgram::parse(&source)
+        .map_err(|e| syn::Error::new(path.span(), format!("{}:{e}", path.value())))?;
+    Ok((full_path, program))
+}
+
+/// The shader's own name, so renaming it in the shader renames the field. Rust keywords
+/// become raw identifiers, except the few that can't be, which are an error on `lit`.
+fn field_ident(name: &str, lit: &LitStr) -> syn::Result<Ident> {
+    if let Ok(ident) = syn::parse_str::<Ident>(name) {
+        return Ok(ident);
+    }
+    if matches!(name, "self" | "Self" | "super" | "crate" | "_") {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!("`{name}` can't be a Rust field name; rename it in the shader"),
+        ));
+    }
+    Ok(format_ident!("r#{name}"))
+}
+
+fn setter(type_: &CgType) -> Option<String> {
+    let float = |s: &Scalar| matches!(s, Scalar::Float | Scalar::Half | Scalar::Fixed);
+    Some(match type_ {
+        CgType::Scalar(s) if float(s) => "Uniform1fv".into(),
+        CgType::Vector(s, n) if float(s) => format!("Uniform{n}fv"),
+        CgType::Scalar(_) | CgType::Sampler2D | CgType::SamplerCube => "Uniform1iv".into(),
+        CgType::Vector(_, n) => format!("Uniform{n}iv"),
+        CgType::Matrix(s, rows, columns) if float(s) && rows == columns && *rows > 1 => {
+            format!("UniformMatrix{rows}fv")
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,69 @@
+//! Derive macros for `vita_gl_helpers`. Use them through that crate's re-exports.
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, parse_macro_input};
+
+mod attrs;
+mod cg_tables;
+mod table;
+mod vertex;
+
+/// Implements `vita_gl_helpers::vertex::Vertex` for a `#[repr(C)]` struct, which may
+/// have other `repr` options too, like `#[repr(C, align(16))]`.
+///
+/// Each field becomes an attribute named after the field, with its format taken from the
+/// field's `VertexAttribute` impl. Field attributes:
+/// - `#[gl(name = "aPos")]` uses a different attribute name.
+/// - `#[gl(normalized)]` normalizes integer fields.
+/// - `#[gl(format = EXPR)]` uses an explicit `AttributeFormat` instead. Combined with
+///   `normalized`, the format is normalized whatever it says.
+#[proc_macro_derive(Vertex, attributes(gl))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `vita_gl_helpers::attribute::AttributeTable` for a struct of `Attribute`s.
+///
+/// Each field is looked up by its own name, or by `#[gl(name = "aPos")]`.
+/// `#[gl(optional)]` fields don't fail the lookup when the shader lacks them.
+/// `#[gl(skip)]` fields are left out and filled with `Default::default()`.
+#[proc_macro_derive(AttributeTable, attributes(gl))]
+pub fn derive_attribute_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    table::derive_attribute_table(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `vita_gl_helpers::uniforms::UniformTable` for a struct of uniform setters.
+///
+/// Field attributes work as for [`macro@AttributeTable`]; field types must implement
+/// `vita_gl_helpers::uniforms::Uniform`. `#[gl(len = 4)]` states an array length for
+/// `UniformTable::with_locations_checked` to check.
+#[proc_macro_derive(UniformTable, attributes(gl))]
+pub fn derive_uniform_table(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    table::derive_uniform_table(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generates an `AttributeTable` and a `UniformTable` from a vertex and fragment `.cg` file.
+///
+/// ```ignore
+/// cg_tables!("shaders/vert.cg", "shaders/frag.cg", attributes = MyAttributes, uniforms = MyUniforms);
+/// ```
+/// Paths are relative to the crate's `Cargo.toml`. The structs are named `Attributes` and
+/// `Uniforms` unless named otherwise. Each field is named exactly as in the shader, so
+/// renaming a uniform in the shader breaks the code that sets it at compile time.
+/// The attributes are the vertex shader's inputs; the uniforms come from both shaders.
+#[proc_macro]
+pub fn cg_tables(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as cg_tables::CgTablesInput);
+    cg_tables::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
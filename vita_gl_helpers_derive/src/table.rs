@@ -0,0 +1,189 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Index, LitInt, LitStr, Member, Type};
+
+use crate::attrs::GlFieldAttrs;
+
+/// A field of an `AttributeTable` or `UniformTable` struct.
+pub struct TableField<'a> {
+    pub member: Member,
+    pub ty: &'a Type,
+    /// `None` for `#[gl(skip)]` fields, which are filled in with `Default::default()`.
+    pub gl_name: Option<LitStr>,
+    pub optional: bool,
+    pub len: Option<LitInt>,
+}
+
+pub fn table_fields<'a>(
+    input: &'a DeriveInput,
+    derive: &str,
+    allowed: &[&str],
+) -> syn::Result<Vec<TableField<'a>>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            format!("derive({derive}) only supports structs"),
+        ));
+    };
+    let mut out = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let attrs = GlFieldAttrs::parse(&field.attrs)?;
+        attrs.allow_only(derive, allowed)?;
+        let member = match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        };
+        let gl_name = if attrs.skip {
+            None
+        } else {
+            Some(attrs.gl_name(field)?)
+        };
+        out.push(TableField {
+            member,
+            ty: &field.ty,
+            gl_name,
+            optional: attrs.optional,
+            len: attrs.len,
+        });
+    }
+    Ok(out)
+}
+
+/// Builds `Self { .. }` from one expression per field, working for tuple and unit structs too.
+pub fn construct(
+    input: &DeriveInput,
+    fields: &[TableField],
+    values: &[TokenStream],
+) -> TokenStream {
+    let Data::Struct(data) = &input.data else {
+        unreachable!()
+    };
+    let members = fields.iter().map(|f| &f.member);
+    match data.fields {
+        Fields::Unit => quote! { Self },
+        _ => quote! { Self { #(#members: #values),* } },
+    }
+}
+
+/// Shared by both table derives: look up every named field, then build `Self` from the locations.
+pub fn lookup(
+    input: &DeriveInput,
+    fields: &[TableField],
+    get_location: TokenStream,
+    make: impl Fn(&TableField, TokenStream) -> TokenStream,
+) -> TokenStream {
+    let entries = fields.iter().filter_map(|f| {
+        let optional = f.optional;
+        f.gl_name.as_ref().map(|name| quote! { (#name, #optional) })
+    });
+    let mut i = 0usize;
+    let values: Vec<TokenStream> = fields
+        .iter()
+        .map(|f| match &f.gl_name {
+            Some(_) => {
+                let index = Index::from(i);
+                i += 1;
+                make(f, quote! { locations[#index] })
+            }
+            None => quote! { ::core::default::Default::default() },
+        })
+        .collect();
+    let construct = construct(input, fields, &values);
+    quote! {
+        ::vita_gl_helpers::program::lookup_locations([#(#entries),*], |n| p.#get_location(ctx, n))
+            .map(|locations| #construct)
+    }
+}
+
+pub fn derive_attribute_table(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = table_fields(&input, "AttributeTable", &["name", "skip", "optional"])?;
+    let body = lookup(
+        &input,
+        &fields,
+        quote! { get_attrib_location },
+        |_, location| quote! { ::vita_gl_helpers::attribute::Attribute(#location as u32) },
+    );
+    let named = fields.iter().filter_map(|f| {
+        let member = &f.member;
+        f.gl_name
+            .as_ref()
+            .map(|name| quote! { (#name, &self.#member) })
+    });
+    let descriptions = fields.iter().filter_map(|f| {
+        let optional = f.optional;
+        f.gl_name.as_ref().map(|name| {
+            quote! {
+                ::vita_gl_helpers::attribute::AttributeField {
+                    name: #name,
+                    optional: #optional,
+                }
+            }
+        })
+    });
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::vita_gl_helpers::attribute::AttributeTable for #name #ty_generics #where_clause {
+            fn lookup(
+                ctx: &::vita_gl_helpers::GlContext,
+                p: &::vita_gl_helpers::program::Program,
+            ) -> ::vita_gl_helpers::program::TableLookup<Self> {
+                #body
+            }
+            fn fields() -> ::std::vec::Vec<::vita_gl_helpers::attribute::AttributeField> {
+                ::std::vec![#(#descriptions),*]
+            }
+            fn named_attributes(
+                &self,
+            ) -> impl ::core::iter::Iterator<Item = (&'static str, &::vita_gl_helpers::attribute::Attribute)> {
+                [#(#named),*].into_iter()
+            }
+        }
+    })
+}
+
+pub fn derive_uniform_table(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = table_fields(&input, "UniformTable", &["name", "skip", "optional", "len"])?;
+    let body = lookup(
+        &input,
+        &fields,
+        quote! { get_uniform_location },
+        |f, location| {
+            let ty = f.ty;
+            quote! { <#ty as ::vita_gl_helpers::uniforms::Uniform>::from_location(#location) }
+        },
+    );
+    let descriptions = fields.iter().filter_map(|f| {
+        let ty = f.ty;
+        let optional = f.optional;
+        let size = match &f.len {
+            Some(len) => quote! { ::core::option::Option::Some(#len) },
+            None => quote! { ::core::option::Option::None },
+        };
+        f.gl_name.as_ref().map(|name| {
+            quote! {
+                ::vita_gl_helpers::uniforms::UniformField {
+                    name: #name,
+                    types: <#ty as ::vita_gl_helpers::uniforms::Uniform>::TYPES,
+                    size: #size,
+                    optional: #optional,
+                }
+            }
+        })
+    });
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::vita_gl_helpers::uniforms::UniformTable for #name #ty_generics #where_clause {
+            fn lookup(
+                ctx: &::vita_gl_helpers::GlContext,
+                p: &::vita_gl_helpers::program::Program,
+            ) -> ::vita_gl_helpers::program::TableLookup<Self> {
+                #body
+            }
+            fn fields() -> ::std::vec::Vec<::vita_gl_helpers::uniforms::UniformField> {
+                ::std::vec![#(#descriptions),*]
+            }
+        }
+    })
+}
@@ -0,0 +1,83 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, spanned::Spanned};
+
+use crate::attrs::GlFieldAttrs;
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    if !repr_c(&input)? {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "derive(Vertex) requires #[repr(C)]",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "derive(Vertex) only supports structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "derive(Vertex) needs named fields",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut entries = Vec::new();
+    for field in &fields.named {
+        let attrs = GlFieldAttrs::parse(&field.attrs)?;
+        attrs.allow_only("Vertex", &["name", "normalized", "format"])?;
+        let gl_name = attrs.gl_name(field)?;
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let format = match &attrs.format {
+            Some(format) => quote! { #format },
+            None => quote! { <#ty as ::vita_gl_helpers::vertex::VertexAttribute>::FORMAT },
+        };
+        // `normalized` overrides whatever the format says.
+        let format = if attrs.normalized {
+            quote! {
+                ::vita_gl_helpers::attribute::AttributeFormat {
+                    normalized: true,
+                    ..#format
+                }
+            }
+        } else {
+            format
+        };
+        entries.push(quote! {
+            ::vita_gl_helpers::vertex::VertexField {
+                name: #gl_name,
+                format: #format,
+                offset: ::core::mem::offset_of!(Self, #ident),
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::vita_gl_helpers::vertex::Vertex for #name #ty_generics #where_clause {
+            const FIELDS: &'static [::vita_gl_helpers::vertex::VertexField] = &[#(#entries),*];
+        }
+    })
+}
+
+/// Whether any `#[repr(...)]` lists `C`, alongside whatever else, e.g. `align(16)`.
+fn repr_c(input: &DeriveInput) -> syn::Result<bool> {
+    let mut repr_c = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") {
+                repr_c = true;
+            } else if meta.input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr_c)
+}